@@ -0,0 +1,21 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use daedalus::get_path_from_artifact;
+
+fn path_from_artifact(c: &mut Criterion) {
+    let mut group = c.benchmark_group("get_path_from_artifact");
+
+    for artifact in [
+        "com.mojang:brigadier:1.0.18",
+        "org.lwjgl:lwjgl:3.3.1:natives-windows-x86",
+        "net.minecraftforge:forge:1.19.2-43.2.0:installer@zip",
+    ] {
+        group.bench_function(artifact, |b| {
+            b.iter(|| get_path_from_artifact(black_box(artifact)))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, path_from_artifact);
+criterion_main!(benches);
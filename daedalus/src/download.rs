@@ -0,0 +1,1365 @@
+use crate::minecraft::{Asset, Download, Library, RESOURCES_BASE_URL};
+use crate::{fetch_err, get_file_hash, get_hash, Branding, Error, BRANDING};
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Semaphore;
+
+pub use tokio_util::sync::CancellationToken;
+
+/// The downloader used by the fetch functions of this crate
+pub static DOWNLOADER: OnceCell<Downloader> = OnceCell::new();
+
+/// A ranking of mirrors along with the time it was computed at
+type MirrorRanking = (Instant, Vec<String>);
+
+/// The mirror rankings computed by [`Downloader::rank_mirrors`], by the mirror
+/// list they were computed for. Clones share the rankings
+#[derive(Debug, Clone, Default)]
+pub struct MirrorRankings(Arc<Mutex<HashMap<Vec<String>, MirrorRanking>>>);
+
+/// The number of requests which picked a user agent of a pool, deciding which
+/// one the next request uses
+static USER_AGENT_ROTATION: AtomicUsize = AtomicUsize::new(0);
+
+/// A client which sends each request with the next user agent of its pool
+#[derive(Debug, Clone)]
+struct RotatingClient {
+    client: reqwest::Client,
+    user_agents: Vec<reqwest::header::HeaderValue>,
+}
+
+impl RotatingClient {
+    /// Starts a request, picking its user agent
+    fn request(
+        &self,
+        method: reqwest::Method,
+        url: &str,
+    ) -> reqwest::RequestBuilder {
+        let request = self.client.request(method, url);
+        let user_agent = match &*self.user_agents {
+            [] => return request,
+            [user_agent] => user_agent,
+            user_agents => {
+                let index = USER_AGENT_ROTATION.fetch_add(1, Ordering::Relaxed);
+
+                &user_agents[index % user_agents.len()]
+            }
+        };
+
+        request.header(reqwest::header::USER_AGENT, user_agent.clone())
+    }
+
+    fn get(&self, url: &str) -> reqwest::RequestBuilder {
+        self.request(reqwest::Method::GET, url)
+    }
+
+    fn head(&self, url: &str) -> reqwest::RequestBuilder {
+        self.request(reqwest::Method::HEAD, url)
+    }
+}
+
+/// A hook receiving metrics about the downloads of a [`Downloader`], for example
+/// to feed them into a monitoring system. All methods do nothing by default
+pub trait DownloadMetrics: std::fmt::Debug + Send + Sync {
+    /// Called when a request is sent to the host
+    fn on_request(&self, _host: &str) {}
+    /// Called when a response body of the given amount of bytes was received from the host
+    fn on_bytes(&self, _host: &str, _bytes: u64) {}
+    /// Called when a download from the host failed and is about to be retried
+    fn on_retry(&self, _host: &str, _attempt: u32) {}
+    /// Called when a download from the host failed for good
+    fn on_failure(&self, _host: &str, _error: &Error) {}
+}
+
+/// What a server reports about a file, see [`Downloader::probe_file`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileProbe {
+    /// Whether the file exists on the server
+    pub exists: bool,
+    /// The size of the file, if the server reported it
+    pub size: Option<u64>,
+    /// The `ETag` header of the file, if the server sent one
+    pub etag: Option<String>,
+    /// The `Last-Modified` header of the file, if the server sent one
+    pub last_modified: Option<String>,
+}
+
+impl FileProbe {
+    /// Reads the probe of an existing file from the headers of a response
+    fn from_headers(
+        headers: &reqwest::header::HeaderMap,
+        size: Option<u64>,
+    ) -> FileProbe {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|x: &reqwest::header::HeaderValue| x.to_str().ok())
+                .map(|x| x.to_string())
+        };
+
+        FileProbe {
+            exists: true,
+            size,
+            etag: header(reqwest::header::ETAG),
+            last_modified: header(reqwest::header::LAST_MODIFIED),
+        }
+    }
+}
+
+/// A file to download to a destination with [`Downloader::download_files`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileDownload {
+    /// The URL of the file
+    pub url: String,
+    /// The SHA1 hash of the file, if it is known
+    pub sha1: Option<String>,
+    /// The path the file is downloaded to
+    pub destination: PathBuf,
+}
+
+/// The validators of a downloaded file, which are sent along with the next
+/// download of it to only receive it again if it has changed, see
+/// [`Downloader::download_file_conditional`]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Validator {
+    /// The `ETag` header of the file, if the server sent one
+    pub etag: Option<String>,
+    /// The `Last-Modified` header of the file, if the server sent one
+    pub last_modified: Option<String>,
+}
+
+impl Validator {
+    /// Reads the validators from the headers of a response
+    fn from_headers(headers: &reqwest::header::HeaderMap) -> Validator {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|x: &reqwest::header::HeaderValue| x.to_str().ok())
+                .map(|x| x.to_string())
+        };
+
+        Validator {
+            etag: header(reqwest::header::ETAG),
+            last_modified: header(reqwest::header::LAST_MODIFIED),
+        }
+    }
+}
+
+/// The result of a conditional download
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Conditional<T> {
+    /// The file has not changed since the download the validator is from
+    NotModified,
+    /// The file has changed, or there was no validator to compare it against
+    Modified {
+        /// The downloaded file, or what was parsed from it
+        value: T,
+        /// The validator to send with the next download of the file
+        validator: Validator,
+    },
+}
+
+impl<T> Conditional<T> {
+    /// Converts the value of a modified file, such as by parsing it
+    pub fn try_map<U, E>(
+        self,
+        f: impl FnOnce(T) -> Result<U, E>,
+    ) -> Result<Conditional<U>, E> {
+        Ok(match self {
+            Conditional::NotModified => Conditional::NotModified,
+            Conditional::Modified { value, validator } => {
+                Conditional::Modified {
+                    value: f(value)?,
+                    validator,
+                }
+            }
+        })
+    }
+}
+
+/// How a [`Downloader`] treats files which don't match their checksum
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ChecksumPolicy {
+    /// Files are retried and fail with [`Error::ChecksumFailure`] if they keep
+    /// mismatching
+    #[default]
+    Enforce,
+    /// Mismatches are logged as warnings and the files are accepted. This is
+    /// meant for development against mirrors whose hashes are out of sync
+    WarnOnly,
+    /// Files aren't hashed at all
+    Skip,
+}
+
+/// The kind of file being downloaded, deciding how long a request may take
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DownloadKind {
+    /// Small documents like manifests and version infos, which should fail fast,
+    /// see [`Downloader::metadata_timeout`]
+    Metadata,
+    /// Potentially large files like libraries, assets and game jars,
+    /// see [`Downloader::artifact_timeout`]
+    Artifact,
+}
+
+/// Configuration for downloading files with retry and checksum functionality
+#[derive(Debug, Clone)]
+pub struct Downloader {
+    /// The maximum amount of time a download may take across all of its retries.
+    /// If this is not set, only the timeout of each single request applies
+    pub overall_timeout: Option<Duration>,
+    /// The timeout of each single request of a metadata download
+    pub metadata_timeout: Duration,
+    /// The timeout of each single request of an artifact download, which
+    /// defaults to 120 seconds. This also applies to
+    /// [`Downloader::download_file`], which used to time out every request
+    /// after 15 seconds; set this to 15 seconds to keep that behaviour
+    pub artifact_timeout: Duration,
+    /// The hook receiving metrics about the downloads, if any
+    pub metrics: Option<Arc<dyn DownloadMetrics>>,
+    /// How long a ranking computed by [`Downloader::rank_mirrors`] is reused
+    /// before the mirrors are probed again
+    pub mirror_ranking_ttl: Duration,
+    /// The rankings computed by [`Downloader::rank_mirrors`]. Each downloader
+    /// has its own unless it is cloned or they are shared explicitly
+    pub mirror_rankings: MirrorRankings,
+    /// A semaphore every download acquires a permit from, for the whole
+    /// duration of the download including its retries. Sharing it between
+    /// downloaders or with other code bounds the total amount of concurrent
+    /// downloads. It is the hard ceiling to any per-batch concurrency: a batch
+    /// allowed more concurrent downloads than there are permits left waits for
+    /// them. Waiting for a permit does not count towards the overall timeout
+    pub semaphore: Option<Arc<Semaphore>>,
+    /// Hosts whose files are trusted by their origin, skipping checksum
+    /// validation for them. This is meant for controlled environments with
+    /// mirrors which legitimately change files, such as by recompressing them.
+    /// Files from these hosts are not checked for corruption or tampering, so
+    /// only add hosts which are fully under your control and accessed securely
+    pub trusted_hosts: HashSet<String>,
+    /// The base URL asset objects are downloaded from, which defaults to
+    /// [`RESOURCES_BASE_URL`]. Set this to the URL of a mirror of it to
+    /// redirect asset downloads
+    pub resources_base_url: String,
+    /// How downloads with a mismatching checksum are treated, which defaults
+    /// to [`ChecksumPolicy::Enforce`]
+    pub checksum_policy: ChecksumPolicy,
+    /// A pool of user agents the requests rotate through round-robin, for
+    /// tools mirroring large amounts of files. If it is empty, which is the
+    /// default, every request uses the user agent of the [`BRANDING`]. Only
+    /// use this responsibly and with the permission of the hosts, as it
+    /// spreads the requests across identities the hosts may rate limit by
+    pub user_agents: Vec<String>,
+    /// The base URLs of the maven repositories [`Downloader::download_library`]
+    /// falls back to, in order, after the URL of the artifact of a library and
+    /// its own repository. Defaults to [`DEFAULT_LIBRARY_REPOSITORIES`]
+    pub library_repositories: Vec<String>,
+    /// The local address requests are sent from, if any. Binding to
+    /// [`Ipv4Addr::UNSPECIFIED`](std::net::Ipv4Addr::UNSPECIFIED) or
+    /// [`Ipv6Addr::UNSPECIFIED`](std::net::Ipv6Addr::UNSPECIFIED) restricts
+    /// the requests to IPv4 or IPv6, for networks where the other one is
+    /// broken. If it is not set, which is the default, the system decides
+    pub local_address: Option<std::net::IpAddr>,
+    /// The most bytes a file downloaded into memory may have, if any. This
+    /// bounds how much memory a server sending a far larger file than
+    /// expected can take up. Files whose size is known, such as assets,
+    /// libraries, game jars and asset indexes, are also bounded by that size.
+    /// Downloads straight to files aren't bounded. Exceeding the bound fails
+    /// the download with [`Error::ResponseTooLarge`]
+    pub max_size: Option<u64>,
+}
+
+/// The maven repositories libraries are looked up in by default: the
+/// libraries of Mojang and Maven Central
+pub const DEFAULT_LIBRARY_REPOSITORIES: &[&str] = &[
+    "https://libraries.minecraft.net/",
+    "https://repo1.maven.org/maven2/",
+];
+
+impl Default for Downloader {
+    fn default() -> Self {
+        Downloader {
+            overall_timeout: None,
+            metadata_timeout: Duration::from_secs(10),
+            artifact_timeout: Duration::from_secs(120),
+            metrics: None,
+            mirror_ranking_ttl: Duration::from_secs(10 * 60),
+            mirror_rankings: MirrorRankings::default(),
+            semaphore: None,
+            trusted_hosts: HashSet::new(),
+            resources_base_url: RESOURCES_BASE_URL.to_string(),
+            checksum_policy: ChecksumPolicy::Enforce,
+            user_agents: Vec::new(),
+            library_repositories: DEFAULT_LIBRARY_REPOSITORIES
+                .iter()
+                .map(|x| x.to_string())
+                .collect(),
+            local_address: None,
+            max_size: None,
+        }
+    }
+}
+
+impl Downloader {
+    /// Sets the downloader used by the fetch functions of this crate
+    pub fn set_downloader(downloader: Downloader) -> Result<(), Error> {
+        DOWNLOADER
+            .set(downloader)
+            .map_err(|_| Error::DownloaderAlreadySet)
+    }
+
+    /// Downloads a file from specified mirrors
+    pub async fn download_file_mirrors(
+        &self,
+        base: &str,
+        mirrors: &[&str],
+        sha1: Option<&str>,
+    ) -> Result<bytes::Bytes, Error> {
+        if mirrors.is_empty() {
+            return Err(Error::ParseError("No mirrors provided!".to_string()));
+        }
+
+        for (index, mirror) in mirrors.iter().enumerate() {
+            let result = self
+                .download_file(&format!("{}{}", mirror, base), sha1)
+                .await;
+
+            if result.is_ok()
+                || (result.is_err() && index == (mirrors.len() - 1))
+            {
+                return result;
+            }
+        }
+
+        unreachable!()
+    }
+
+    /// Sorts the mirrors by the latency of a lightweight request to each of
+    /// them, fastest first. Mirrors which could not be reached are placed last,
+    /// in their original order. The ranking is cached for
+    /// [`Downloader::mirror_ranking_ttl`]
+    pub async fn rank_mirrors(&self, mirrors: &[&str]) -> Vec<String> {
+        let key = mirrors.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+
+        if let Some((ranked_at, ranking)) = self
+            .mirror_rankings
+            .0
+            .lock()
+            .ok()
+            .and_then(|x| x.get(&key).cloned())
+        {
+            if ranked_at.elapsed() < self.mirror_ranking_ttl {
+                return ranking;
+            }
+        }
+
+        let mut probes = tokio::task::JoinSet::new();
+        for (index, mirror) in key.iter().enumerate() {
+            let client = match self.client(mirror, DownloadKind::Metadata) {
+                Ok(client) => client,
+                Err(_) => continue,
+            };
+            let mirror = mirror.clone();
+
+            probes.spawn(async move {
+                (index, probe_latency(&client, &mirror).await)
+            });
+        }
+
+        let mut latencies = vec![None; key.len()];
+        while let Some(probe) = probes.join_next().await {
+            if let Ok((index, latency)) = probe {
+                latencies[index] = latency;
+            }
+        }
+
+        let mut ranked = key.iter().zip(latencies).collect::<Vec<_>>();
+        // `None` sorts before `Some`, so unreachable mirrors are keyed to last
+        ranked.sort_by_key(|(_, latency)| (latency.is_none(), *latency));
+        let ranking = ranked
+            .into_iter()
+            .map(|(mirror, _)| mirror.clone())
+            .collect::<Vec<_>>();
+
+        if let Ok(mut rankings) = self.mirror_rankings.0.lock() {
+            rankings.insert(key, (Instant::now(), ranking.clone()));
+        }
+
+        ranking
+    }
+
+    /// Checks whether a file exists and how large it is without downloading
+    /// it, with the same retries as the downloads. A HEAD request is sent
+    /// first, falling back to requesting a single byte for servers which
+    /// reject HEAD
+    pub async fn probe_file(&self, url: &str) -> Result<FileProbe, Error> {
+        self.with_deadline(url, self.probe_attempts(url)).await
+    }
+
+    /// Downloads the files to their destinations like
+    /// [`Downloader::download_to_file`], with up to the given amount of
+    /// downloads at the same time. Stops at the first failed download, aborting
+    /// the others. Once the cancellation token is cancelled, no more downloads
+    /// are started, the running ones are aborted and [`Error::Cancelled`] is
+    /// returned. Aborted downloads leave no file at their destination
+    pub async fn download_files(
+        &self,
+        files: Vec<FileDownload>,
+        concurrency: usize,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<(), Error> {
+        let downloader = Arc::new(self.clone());
+        let mut files = files.into_iter();
+        let mut tasks = tokio::task::JoinSet::new();
+
+        loop {
+            if cancel.is_some_and(|x| x.is_cancelled()) {
+                return Err(Error::Cancelled);
+            }
+
+            while tasks.len() < concurrency.max(1) {
+                let file = match files.next() {
+                    Some(file) => file,
+                    None => break,
+                };
+                let downloader = downloader.clone();
+
+                tasks.spawn(async move {
+                    downloader
+                        .download_to_file(
+                            &file.url,
+                            file.sha1.as_deref(),
+                            &file.destination,
+                        )
+                        .await
+                });
+            }
+
+            let next = match cancel {
+                Some(cancel) => tokio::select! {
+                    _ = cancel.cancelled() => return Err(Error::Cancelled),
+                    next = tasks.join_next() => next,
+                },
+                None => tasks.join_next().await,
+            };
+
+            // Dropping the tasks on an early return aborts the running ones
+            match next {
+                Some(result) => result??,
+                None => return Ok(()),
+            }
+        }
+    }
+
+    /// Downloads an asset object from [`Downloader::resources_base_url`],
+    /// validating it against its hash
+    pub async fn download_asset(
+        &self,
+        asset: &Asset,
+    ) -> Result<bytes::Bytes, Error> {
+        self.download_file_with_size(
+            &asset.url(&self.resources_base_url),
+            Some(&asset.hash),
+            Some(asset.size),
+        )
+        .await
+    }
+
+    /// Downloads a file of a version, such as the game jar of a side from
+    /// [`SidedVersionInfo::jar_download`](crate::minecraft::SidedVersionInfo::jar_download),
+    /// validating it against its hash and bounding it by its size
+    pub async fn download_version_file(
+        &self,
+        download: &Download,
+    ) -> Result<bytes::Bytes, Error> {
+        self.download_file_with_size(
+            &download.url,
+            Some(&download.sha1),
+            Some(download.size),
+        )
+        .await
+    }
+
+    /// Downloads the primary artifact of a library from the first of its
+    /// [`Library::download_candidates`] which has it, with the
+    /// [`Downloader::library_repositories`] as the extra repositories. It is
+    /// validated against the hash of its artifact or its first checksum
+    pub async fn download_library(
+        &self,
+        library: &Library,
+    ) -> Result<bytes::Bytes, Error> {
+        let repositories = self
+            .library_repositories
+            .iter()
+            .map(|x| &**x)
+            .collect::<Vec<_>>();
+        let candidates = library.download_candidates(&repositories);
+        let download = library.resolve_download("");
+        let sha1 = download
+            .as_ref()
+            .map(|x| &*x.sha1)
+            .filter(|x| !x.is_empty());
+        let size = download.as_ref().map(|x| x.size);
+
+        let mut result = Err(Error::ParseError(format!(
+            "No download candidates for library {}",
+            library.name
+        )));
+        for candidate in &candidates {
+            result = self.download_file_with_size(candidate, sha1, size).await;
+
+            if result.is_ok() {
+                break;
+            }
+        }
+
+        result
+    }
+
+    /// Downloads a file with retry and checksum functionality, applying the
+    /// timeout of artifacts, see [`Downloader::artifact_timeout`]
+    pub async fn download_file(
+        &self,
+        url: &str,
+        sha1: Option<&str>,
+    ) -> Result<bytes::Bytes, Error> {
+        self.download_file_with_kind(url, sha1, DownloadKind::Artifact)
+            .await
+    }
+
+    /// Downloads a file with retry and checksum functionality, applying the
+    /// timeout of the kind of file
+    pub async fn download_file_with_kind(
+        &self,
+        url: &str,
+        sha1: Option<&str>,
+        kind: DownloadKind,
+    ) -> Result<bytes::Bytes, Error> {
+        let sha1 = self.checksum(url, sha1);
+
+        self.with_deadline(url, self.download_attempts(url, sha1, None, kind))
+            .await
+            .map(|(bytes, _)| bytes)
+    }
+
+    /// Downloads a file with retry and checksum functionality, failing with
+    /// [`Error::ResponseTooLarge`] if it is larger than its expected size. A
+    /// size of zero is treated as unknown
+    pub async fn download_file_with_size(
+        &self,
+        url: &str,
+        sha1: Option<&str>,
+        size: Option<u64>,
+    ) -> Result<bytes::Bytes, Error> {
+        let sha1 = self.checksum(url, sha1);
+        let size = size.filter(|x| *x != 0);
+
+        self.with_deadline(
+            url,
+            self.download_attempts(url, sha1, size, DownloadKind::Artifact),
+        )
+        .await
+        .map(|(bytes, _)| bytes)
+    }
+
+    /// Downloads a metadata document with the timeout of metadata, bounded
+    /// by its size if it is known and not zero
+    pub(crate) async fn download_metadata(
+        &self,
+        url: &str,
+        sha1: Option<&str>,
+        size: Option<u64>,
+    ) -> Result<bytes::Bytes, Error> {
+        let sha1 = self.checksum(url, sha1);
+        let size = size.filter(|x| *x != 0);
+
+        self.with_deadline(
+            url,
+            self.download_attempts(url, sha1, size, DownloadKind::Metadata),
+        )
+        .await
+        .map(|(bytes, _)| bytes)
+    }
+
+    /// Downloads a file with retry and checksum functionality, returning the
+    /// headers of the successful response alongside the file
+    pub async fn download_file_with_headers(
+        &self,
+        url: &str,
+        sha1: Option<&str>,
+    ) -> Result<(bytes::Bytes, reqwest::header::HeaderMap), Error> {
+        let sha1 = self.checksum(url, sha1);
+
+        self.with_deadline(
+            url,
+            self.download_attempts(url, sha1, None, DownloadKind::Artifact),
+        )
+        .await
+    }
+
+    /// Downloads a metadata file with retry functionality, unless it has not
+    /// changed since the download the validator is from. The server decides
+    /// whether the file changed by its `ETag` or `Last-Modified` header
+    pub async fn download_file_conditional(
+        &self,
+        url: &str,
+        validator: Option<&Validator>,
+    ) -> Result<Conditional<bytes::Bytes>, Error> {
+        self.with_deadline(url, self.conditional_attempts(url, validator))
+            .await
+    }
+
+    /// Downloads a file to the specified path, resuming from the data already
+    /// present at that path by requesting only the remaining bytes.
+    /// If the server ignores the range request, the file is downloaded from
+    /// the start. The checksum is validated against the complete file
+    pub async fn download_file_resumable(
+        &self,
+        url: &str,
+        sha1: Option<&str>,
+        partial_path: &Path,
+    ) -> Result<(), Error> {
+        let sha1 = self.checksum(url, sha1);
+
+        self.with_deadline(
+            url,
+            self.download_resumable_attempts(url, sha1, partial_path),
+        )
+        .await
+    }
+
+    /// Downloads a file to the destination, streaming it into a temporary file
+    /// next to it which is renamed to the destination once its checksum is
+    /// validated, so the destination never holds a partial file. The temporary
+    /// file is removed if the download fails. If the destination already
+    /// exists with the expected checksum, nothing is downloaded. Without a
+    /// checksum, the file is always downloaded
+    pub async fn download_to_file(
+        &self,
+        url: &str,
+        sha1: Option<&str>,
+        dest: &Path,
+    ) -> Result<(), Error> {
+        if let Some(sha1) = sha1 {
+            if tokio::fs::metadata(dest).await.is_ok()
+                && get_file_hash(dest).await?.eq_ignore_ascii_case(sha1)
+            {
+                return Ok(());
+            }
+        }
+
+        if let Some(parent) = dest.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        let mut temp_name = dest
+            .file_name()
+            .ok_or_else(|| {
+                Error::ParseError(format!(
+                    "Invalid destination {} for {}",
+                    dest.display(),
+                    url
+                ))
+            })?
+            .to_os_string();
+        temp_name.push(".part");
+        let temp_path = dest.with_file_name(temp_name);
+
+        // Leftovers of an earlier crash can't be trusted
+        if tokio::fs::metadata(&temp_path).await.is_ok() {
+            tokio::fs::remove_file(&temp_path).await?;
+        }
+
+        let result =
+            match self.download_file_resumable(url, sha1, &temp_path).await {
+                Ok(()) => tokio::fs::rename(&temp_path, dest)
+                    .await
+                    .map_err(Error::from),
+                Err(err) => Err(err),
+            };
+
+        if result.is_err() {
+            let _ = tokio::fs::remove_file(&temp_path).await;
+        }
+
+        result
+    }
+
+    /// Applies the concurrency limit and the overall timeout to a download and
+    /// reports its failure
+    async fn with_deadline<T>(
+        &self,
+        url: &str,
+        download: impl Future<Output = Result<T, Error>>,
+    ) -> Result<T, Error> {
+        let _permit = match &self.semaphore {
+            Some(semaphore) => Some(semaphore.acquire().await?),
+            None => None,
+        };
+
+        let result = if let Some(overall_timeout) = self.overall_timeout {
+            let start = Instant::now();
+
+            tokio::time::timeout(overall_timeout, download)
+                .await
+                .map_err(|_| Error::Timeout {
+                    url: url.to_string(),
+                    elapsed: start.elapsed(),
+                })
+                .and_then(|x| x)
+        } else {
+            download.await
+        };
+
+        if let Err(err) = &result {
+            self.record(url, |metrics, host| metrics.on_failure(host, err));
+        }
+
+        result
+    }
+
+    /// Returns the checksum to validate the file at the URL against, which is
+    /// none if the URL belongs to a trusted host or checksums are skipped
+    fn checksum<'a>(
+        &self,
+        url: &str,
+        sha1: Option<&'a str>,
+    ) -> Option<&'a str> {
+        if self.checksum_policy == ChecksumPolicy::Skip {
+            return None;
+        }
+        if self.trusted_hosts.is_empty() {
+            return sha1;
+        }
+
+        let trusted = reqwest::Url::parse(url)
+            .ok()
+            .and_then(|x| x.host_str().map(|x| self.trusted_hosts.contains(x)))
+            .unwrap_or(false);
+
+        sha1.filter(|_| !trusted)
+    }
+
+    /// Logs a checksum mismatch if the policy only warns about them, returning
+    /// whether the file should be accepted anyway
+    fn warn_on_mismatch(&self, url: &str, expected: &str, found: &str) -> bool {
+        if self.checksum_policy != ChecksumPolicy::WarnOnly {
+            return false;
+        }
+
+        tracing::warn!(
+            url,
+            expected,
+            found,
+            "Checksum mismatch, accepting the file anyway"
+        );
+
+        true
+    }
+
+    /// Passes the host of the URL to the metrics hook, if there is one
+    fn record<F: FnOnce(&dyn DownloadMetrics, &str)>(&self, url: &str, f: F) {
+        if let Some(metrics) = &self.metrics {
+            let host = reqwest::Url::parse(url)
+                .ok()
+                .and_then(|x| x.host_str().map(|x| x.to_string()))
+                .unwrap_or_default();
+
+            f(metrics.as_ref(), &host)
+        }
+    }
+
+    fn client(
+        &self,
+        url: &str,
+        kind: DownloadKind,
+    ) -> Result<RotatingClient, Error> {
+        let user_agents = if self.user_agents.is_empty() {
+            std::slice::from_ref(
+                &BRANDING.get_or_init(Branding::default).header_value,
+            )
+        } else {
+            &self.user_agents[..]
+        };
+
+        let client = reqwest::Client::builder()
+            .tcp_keepalive(Some(Duration::from_secs(10)))
+            .local_address(self.local_address)
+            .timeout(match kind {
+                DownloadKind::Metadata => self.metadata_timeout,
+                DownloadKind::Artifact => self.artifact_timeout,
+            })
+            .build()
+            .map_err(fetch_err(url))?;
+
+        Ok(RotatingClient {
+            client,
+            user_agents: user_agents
+                .iter()
+                .filter_map(|x| reqwest::header::HeaderValue::from_str(x).ok())
+                .collect(),
+        })
+    }
+
+    async fn download_resumable_attempts(
+        &self,
+        url: &str,
+        sha1: Option<&str>,
+        partial_path: &Path,
+    ) -> Result<(), Error> {
+        let client = self.client(url, DownloadKind::Artifact)?;
+
+        for attempt in 1..=4 {
+            match self.resume_download(&client, url, partial_path).await {
+                Ok(()) => {
+                    if let Some(sha1) = sha1 {
+                        let hash = get_file_hash(partial_path).await?;
+                        if !hash.eq_ignore_ascii_case(sha1) {
+                            if self.warn_on_mismatch(url, sha1, &hash) {
+                                return Ok(());
+                            }
+
+                            // The partial data can't be trusted anymore, so
+                            // start over from scratch
+                            tokio::fs::remove_file(partial_path).await?;
+
+                            if attempt <= 3 {
+                                self.record(url, |metrics, host| {
+                                    metrics.on_retry(host, attempt)
+                                });
+                                continue;
+                            } else {
+                                return Err(Error::ChecksumFailure {
+                                    hash: sha1.to_string(),
+                                    url: url.to_string(),
+                                    tries: attempt,
+                                });
+                            }
+                        }
+                    }
+
+                    return Ok(());
+                }
+                Err(_) if attempt <= 3 => {
+                    self.record(url, |metrics, host| {
+                        metrics.on_retry(host, attempt)
+                    });
+                    continue;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        unreachable!()
+    }
+
+    /// Requests the bytes missing from the partial file and writes them to it
+    async fn resume_download(
+        &self,
+        client: &RotatingClient,
+        url: &str,
+        partial_path: &Path,
+    ) -> Result<(), Error> {
+        let offset = tokio::fs::metadata(partial_path)
+            .await
+            .map(|x| x.len())
+            .unwrap_or(0);
+
+        let mut request = client.get(url);
+        if offset > 0 {
+            request = request
+                .header(reqwest::header::RANGE, format!("bytes={}-", offset));
+        }
+
+        self.record(url, |metrics, host| metrics.on_request(host));
+        let mut response = request.send().await.map_err(fetch_err(url))?;
+
+        let mut file = match response.status() {
+            reqwest::StatusCode::PARTIAL_CONTENT => {
+                tokio::fs::OpenOptions::new()
+                    .append(true)
+                    .open(partial_path)
+                    .await?
+            }
+            // The partial file already contains the whole file
+            reqwest::StatusCode::RANGE_NOT_SATISFIABLE if offset > 0 => {
+                return Ok(())
+            }
+            _ => {
+                response =
+                    response.error_for_status().map_err(fetch_err(url))?;
+
+                tokio::fs::File::create(partial_path).await?
+            }
+        };
+
+        while let Some(chunk) =
+            response.chunk().await.map_err(fetch_err(url))?
+        {
+            self.record(url, |metrics, host| {
+                metrics.on_bytes(host, chunk.len() as u64)
+            });
+            file.write_all(&chunk).await?;
+        }
+        file.flush().await?;
+
+        Ok(())
+    }
+
+    async fn download_attempts(
+        &self,
+        url: &str,
+        sha1: Option<&str>,
+        size: Option<u64>,
+        kind: DownloadKind,
+    ) -> Result<(bytes::Bytes, reqwest::header::HeaderMap), Error> {
+        let client = self.client(url, kind)?;
+        let limit = match (size, self.max_size) {
+            (Some(size), Some(max_size)) => Some(size.min(max_size)),
+            (size, max_size) => size.or(max_size),
+        };
+
+        for attempt in 1..=4 {
+            self.record(url, |metrics, host| metrics.on_request(host));
+            let result = client
+                .get(url)
+                .send()
+                .await
+                .and_then(|x| x.error_for_status());
+
+            match result {
+                Ok(x) => {
+                    let headers = x.headers().clone();
+                    let bytes = read_limited(x, url, limit).await?;
+
+                    if let Ok(bytes) = bytes {
+                        self.record(url, |metrics, host| {
+                            metrics.on_bytes(host, bytes.len() as u64)
+                        });
+
+                        if let Some(sha1) = sha1 {
+                            let hash = get_hash(bytes.clone()).await?;
+                            if !hash.eq_ignore_ascii_case(sha1) {
+                                if self.warn_on_mismatch(url, sha1, &hash) {
+                                    return Ok((bytes, headers));
+                                }
+
+                                if attempt <= 3 {
+                                    self.record(url, |metrics, host| {
+                                        metrics.on_retry(host, attempt)
+                                    });
+                                    continue;
+                                } else {
+                                    return Err(Error::ChecksumFailure {
+                                        hash: sha1.to_string(),
+                                        url: url.to_string(),
+                                        tries: attempt,
+                                    });
+                                }
+                            }
+                        }
+
+                        return Ok((bytes, headers));
+                    } else if attempt <= 3 {
+                        self.record(url, |metrics, host| {
+                            metrics.on_retry(host, attempt)
+                        });
+                        continue;
+                    } else if let Err(err) = bytes {
+                        return Err(fetch_err(url)(err));
+                    }
+                }
+                // Client errors won't go away by asking again
+                Err(err)
+                    if attempt <= 3
+                        && !err
+                            .status()
+                            .is_some_and(|x| x.is_client_error()) =>
+                {
+                    self.record(url, |metrics, host| {
+                        metrics.on_retry(host, attempt)
+                    });
+                    continue;
+                }
+                Err(err) => return Err(fetch_err(url)(err)),
+            }
+        }
+
+        unreachable!()
+    }
+
+    /// Downloads a file conditionally, retrying on failure
+    async fn conditional_attempts(
+        &self,
+        url: &str,
+        validator: Option<&Validator>,
+    ) -> Result<Conditional<bytes::Bytes>, Error> {
+        let client = self.client(url, DownloadKind::Metadata)?;
+
+        for attempt in 1..=4 {
+            self.record(url, |metrics, host| metrics.on_request(host));
+
+            let mut request = client.get(url);
+            if let Some(etag) = validator.and_then(|x| x.etag.as_deref()) {
+                request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) =
+                validator.and_then(|x| x.last_modified.as_deref())
+            {
+                request = request
+                    .header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+            }
+
+            let result = match request.send().await {
+                Ok(x) if x.status() == reqwest::StatusCode::NOT_MODIFIED => {
+                    return Ok(Conditional::NotModified)
+                }
+                Ok(x) => match x.error_for_status() {
+                    Ok(x) => {
+                        let validator = Validator::from_headers(x.headers());
+
+                        x.bytes().await.map(|value| (value, validator))
+                    }
+                    Err(err) => Err(err),
+                },
+                Err(err) => Err(err),
+            };
+
+            match result {
+                Ok((value, validator)) => {
+                    self.record(url, |metrics, host| {
+                        metrics.on_bytes(host, value.len() as u64)
+                    });
+
+                    return Ok(Conditional::Modified { value, validator });
+                }
+                // Client errors won't go away by asking again
+                Err(err)
+                    if attempt <= 3
+                        && !err
+                            .status()
+                            .is_some_and(|x| x.is_client_error()) =>
+                {
+                    self.record(url, |metrics, host| {
+                        metrics.on_retry(host, attempt)
+                    });
+                    continue;
+                }
+                Err(err) => return Err(fetch_err(url)(err)),
+            }
+        }
+
+        unreachable!()
+    }
+
+    /// Probes a file, retrying on failure
+    async fn probe_attempts(&self, url: &str) -> Result<FileProbe, Error> {
+        let client = self.client(url, DownloadKind::Metadata)?;
+
+        for attempt in 1..=4 {
+            self.record(url, |metrics, host| metrics.on_request(host));
+
+            match probe(&client, url).await {
+                Ok(probe) => return Ok(probe),
+                Err(_) if attempt <= 3 => {
+                    self.record(url, |metrics, host| {
+                        metrics.on_retry(host, attempt)
+                    });
+                    continue;
+                }
+                Err(err) => return Err(fetch_err(url)(err)),
+            }
+        }
+
+        unreachable!()
+    }
+}
+
+/// Reads the body of a response, failing with [`Error::ResponseTooLarge`] as
+/// soon as it exceeds the limit. Errors of the connection are returned as the
+/// inner error, so the download can be retried
+async fn read_limited(
+    mut response: reqwest::Response,
+    url: &str,
+    limit: Option<u64>,
+) -> Result<Result<bytes::Bytes, reqwest::Error>, Error> {
+    let limit = match limit {
+        Some(limit) => limit,
+        None => return Ok(response.bytes().await),
+    };
+    let too_large = || Error::ResponseTooLarge {
+        url: url.to_string(),
+        limit,
+    };
+
+    if response.content_length().is_some_and(|x| x > limit) {
+        return Err(too_large());
+    }
+
+    let mut body = bytes::BytesMut::new();
+    loop {
+        match response.chunk().await {
+            Ok(Some(chunk)) => {
+                if body.len() as u64 + chunk.len() as u64 > limit {
+                    return Err(too_large());
+                }
+                body.extend_from_slice(&chunk);
+            }
+            Ok(None) => return Ok(Ok(body.freeze())),
+            Err(err) => return Ok(Err(err)),
+        }
+    }
+}
+
+/// Sends a HEAD request for the file, falling back to requesting its first
+/// byte if the server rejects it. Server errors are returned as errors
+async fn probe(
+    client: &RotatingClient,
+    url: &str,
+) -> Result<FileProbe, reqwest::Error> {
+    use reqwest::StatusCode;
+
+    let head = client.head(url).send().await?;
+    let status = head.status();
+
+    if status.is_success() {
+        let size = head
+            .headers()
+            .get(reqwest::header::CONTENT_LENGTH)
+            .and_then(|x| x.to_str().ok())
+            .and_then(|x| x.parse().ok());
+
+        return Ok(FileProbe::from_headers(head.headers(), size));
+    } else if status == StatusCode::NOT_FOUND || status == StatusCode::GONE {
+        return Ok(FileProbe::default());
+    }
+
+    let response = client
+        .get(url)
+        .header(reqwest::header::RANGE, "bytes=0-0")
+        .send()
+        .await?;
+    let status = response.status();
+
+    if status == StatusCode::OK {
+        let size = response.content_length();
+
+        Ok(FileProbe::from_headers(response.headers(), size))
+    } else if status == StatusCode::PARTIAL_CONTENT
+        || status == StatusCode::RANGE_NOT_SATISFIABLE
+    {
+        // The total size follows the slash, as in `bytes 0-0/1234`
+        let size = response
+            .headers()
+            .get(reqwest::header::CONTENT_RANGE)
+            .and_then(|x| x.to_str().ok())
+            .and_then(|x| x.rsplit('/').next())
+            .and_then(|x| x.parse().ok());
+
+        Ok(FileProbe::from_headers(response.headers(), size))
+    } else if status.is_client_error() {
+        Ok(FileProbe::default())
+    } else {
+        response.error_for_status().map(|_| FileProbe::default())
+    }
+}
+
+/// Measures how long the mirror takes to answer a HEAD request, falling back to
+/// requesting a single byte for servers which don't support HEAD
+async fn probe_latency(
+    client: &RotatingClient,
+    mirror: &str,
+) -> Option<Duration> {
+    let start = Instant::now();
+    let head = client.head(mirror).send().await;
+
+    match head {
+        Ok(x) if x.status().is_success() => return Some(start.elapsed()),
+        Err(err) if err.is_connect() || err.is_timeout() => return None,
+        _ => {}
+    }
+
+    let start = Instant::now();
+    client
+        .get(mirror)
+        .header(reqwest::header::RANGE, "bytes=0-0")
+        .send()
+        .await
+        .ok()
+        .filter(|x| !x.status().is_server_error())
+        .map(|_| start.elapsed())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    pub(crate) type Requests = Arc<Mutex<Vec<String>>>;
+
+    /// Serves one response per connection with the given statuses, in order,
+    /// returning the URL of the server and the requests it received
+    async fn serve(statuses: &[u16]) -> (String, Requests) {
+        serve_body(statuses, "body").await
+    }
+
+    /// Like [`serve`], responding with the body to every status except 304
+    pub(crate) async fn serve_body(
+        statuses: &[u16],
+        body: &str,
+    ) -> (String, Requests) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let requests = Requests::default();
+
+        let statuses = statuses.to_vec();
+        let body = body.to_string();
+        let received = requests.clone();
+        tokio::spawn(async move {
+            for status in statuses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buffer = [0; 4096];
+                let read = stream.read(&mut buffer).await.unwrap();
+                received.lock().unwrap().push(
+                    String::from_utf8_lossy(&buffer[..read]).to_lowercase(),
+                );
+
+                let body = if status == 304 { "" } else { &*body };
+                let response = format!(
+                    "HTTP/1.1 {} Status\r\nContent-Length: {}\r\n\
+                     Connection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        (url, requests)
+    }
+
+    #[tokio::test]
+    async fn conditional_not_modified() {
+        let (url, requests) = serve(&[304]).await;
+
+        let result = Downloader::default()
+            .download_file_conditional(&url, Some(&Validator::default()))
+            .await
+            .unwrap();
+
+        assert_eq!(result, Conditional::NotModified);
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn conditional_retries_server_errors() {
+        let (url, requests) = serve(&[503, 500, 200]).await;
+
+        let result = Downloader::default()
+            .download_file_conditional(&url, None)
+            .await
+            .unwrap();
+
+        match result {
+            Conditional::Modified { value, .. } => assert_eq!(value, "body"),
+            Conditional::NotModified => panic!("the file was modified"),
+        }
+        assert_eq!(requests.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn conditional_fails_on_client_errors() {
+        let (url, requests) = serve(&[404, 200]).await;
+
+        let result = Downloader::default()
+            .download_file_conditional(&url, None)
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn user_agents_rotate_per_request() {
+        let (url, requests) = serve(&[500, 500, 200]).await;
+        let downloader = Downloader {
+            user_agents: vec!["first".to_string(), "second".to_string()],
+            ..Downloader::default()
+        };
+
+        downloader
+            .download_file_conditional(&url, None)
+            .await
+            .unwrap();
+
+        let user_agents = requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|x| x.contains("user-agent: first"))
+            .collect::<Vec<_>>();
+        assert_eq!(user_agents, [true, false, true]);
+    }
+
+    #[tokio::test]
+    async fn version_files_are_bounded_by_their_size() {
+        let (url, requests) = serve(&[200]).await;
+        let download = Download {
+            sha1: "da39a3ee5e6b4b0d3255bfef95601890afd80709".to_string(),
+            size: 2,
+            url,
+        };
+
+        let result =
+            Downloader::default().download_version_file(&download).await;
+
+        assert!(matches!(
+            result,
+            Err(Error::ResponseTooLarge { limit: 2, .. })
+        ));
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn download_retries_server_errors() {
+        let (url, requests) = serve(&[502, 200]).await;
+
+        let bytes = Downloader::default().download_file(&url, None).await;
+
+        assert_eq!(bytes.unwrap(), "body");
+        assert_eq!(requests.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn download_fails_on_client_errors() {
+        let (url, requests) = serve(&[404, 200]).await;
+
+        let result = Downloader::default()
+            .download_file_with_headers(&url, None)
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn mirror_rankings_belong_to_their_downloader() {
+        let (url, requests) = serve(&[200, 200]).await;
+        let downloader = Downloader::default();
+
+        downloader.rank_mirrors(&[&url]).await;
+        downloader.clone().rank_mirrors(&[&url]).await;
+        assert_eq!(requests.lock().unwrap().len(), 1);
+
+        Downloader::default().rank_mirrors(&[&url]).await;
+        assert_eq!(requests.lock().unwrap().len(), 2);
+    }
+}
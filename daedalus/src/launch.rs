@@ -0,0 +1,623 @@
+use crate::layout::{Layout, MojangLayout};
+use crate::minecraft::{
+    default_jvm_arguments, rules_apply, Argument, ArgumentType, ArgumentValue,
+    FeatureSet, LibraryOverrides, Os, VersionInfo,
+};
+use crate::Error;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Returns the separator of the entries of the classpath on the OS
+pub fn classpath_separator(os: &Os) -> &'static str {
+    match os {
+        Os::Windows | Os::WindowsArm64 => ";",
+        _ => ":",
+    }
+}
+
+/// Returns the classpath of the version on the OS with the given features: the
+/// libraries which are included in the classpath, with the overrides applied
+/// and followed by the additive overrides, then the client jar. Join it with
+/// the [`classpath_separator`] of the OS for the `classpath` placeholder
+pub fn classpath(
+    info: &VersionInfo,
+    os: &Os,
+    features: &FeatureSet,
+    layout: &dyn Layout,
+    overrides: &LibraryOverrides,
+) -> Result<Vec<PathBuf>, Error> {
+    let libraries_dir = layout.libraries_dir();
+    let mut paths = Vec::new();
+
+    for library in info
+        .libraries
+        .iter()
+        .filter(|x| x.include_in_classpath && x.applies(os, features))
+    {
+        // Legacy natives-only libraries have no artifact of their own
+        let natives_only = library.natives.is_some()
+            && library
+                .downloads
+                .as_ref()
+                .is_none_or(|x| x.artifact.is_none());
+
+        if overrides.get(library).is_some() || !natives_only {
+            paths.push(overrides.library_path(library, &libraries_dir)?);
+        }
+    }
+
+    paths.extend(overrides.additive_paths(&libraries_dir));
+    paths.push(layout.client_jar_path(&info.id));
+
+    let mut visited = HashSet::new();
+    paths.retain(|x| visited.insert(x.clone()));
+
+    Ok(paths)
+}
+
+/// Returns the substitutions of the placeholders which are derived from the
+/// version, the OS and the layout:
+///
+/// - `classpath_separator`, the separator of the classpath on the OS
+/// - `library_directory`, the libraries directory of the layout
+/// - `natives_directory`, the natives directory of the version in the layout
+/// - `assets_root` and `game_assets`, the assets directory of the layout
+/// - `assets_index_name`, the ID of the assets of the version
+/// - `version_name` and `version_type`, the ID and type of the version
+///
+/// Everything else has to be provided by the launcher, most importantly the
+/// `classpath`, the `game_directory`, the `launcher_name` and
+/// `launcher_version` and the account's `auth_player_name`, `auth_uuid`,
+/// `auth_access_token` and `user_type`
+pub fn platform_substitutions(
+    info: &VersionInfo,
+    os: &Os,
+    layout: &dyn Layout,
+) -> HashMap<String, String> {
+    let path = |x: std::path::PathBuf| x.to_string_lossy().into_owned();
+
+    [
+        ("classpath_separator", classpath_separator(os).to_string()),
+        ("library_directory", path(layout.libraries_dir())),
+        ("natives_directory", path(layout.natives_dir(&info.id))),
+        ("assets_root", path(layout.assets_dir())),
+        ("game_assets", path(layout.assets_dir())),
+        ("assets_index_name", info.assets.clone()),
+        ("version_name", info.id.clone()),
+        ("version_type", info.type_.as_str().to_string()),
+    ]
+    .iter()
+    .map(|(name, value)| (name.to_string(), value.clone()))
+    .collect()
+}
+
+/// Replaces the `${name}` placeholders in the input with their substitutions.
+/// Placeholders without a substitution are kept as they are
+pub fn substitute(
+    input: &str,
+    substitutions: &HashMap<String, String>,
+) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(start) = rest.find("${") {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        match rest.find('}') {
+            Some(end) => {
+                match substitutions.get(&rest[2..end]) {
+                    Some(value) => output.push_str(value),
+                    None => output.push_str(&rest[..=end]),
+                }
+                rest = &rest[end + 1..];
+            }
+            None => break,
+        }
+    }
+    output.push_str(rest);
+
+    output
+}
+
+/// Builds the arguments of the type for the OS with the given features,
+/// evaluating their rules and substituting their placeholders. The
+/// substitutions are the [`platform_substitutions`], overridden by the given
+/// ones, which have to provide the rest.
+///
+/// Versions with only the legacy `minecraft_arguments` string have their game
+/// arguments split from it and the [`default_jvm_arguments`] as their JVM
+/// arguments
+pub fn build_arguments(
+    info: &VersionInfo,
+    type_: ArgumentType,
+    os: &Os,
+    features: &FeatureSet,
+    layout: &dyn Layout,
+    substitutions: &HashMap<String, String>,
+) -> Vec<String> {
+    let mut all_substitutions = platform_substitutions(info, os, layout);
+    all_substitutions.extend(
+        substitutions
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone())),
+    );
+
+    let arguments = info.arguments.as_ref().and_then(|x| x.get(&type_));
+    let arguments = match (arguments, type_) {
+        (Some(arguments), _) => arguments.clone(),
+        (None, ArgumentType::Game) => info
+            .minecraft_arguments
+            .iter()
+            .flat_map(|x| x.split_whitespace())
+            .map(|x| Argument::Normal(x.to_string()))
+            .collect(),
+        (None, ArgumentType::Jvm) => default_jvm_arguments(),
+    };
+
+    let mut built = Vec::new();
+    for argument in arguments {
+        match argument {
+            Argument::Normal(value) => built.push(value),
+            Argument::Ruled { rules, value } => {
+                if rules_apply(&rules, os, features) {
+                    match value {
+                        ArgumentValue::Single(value) => built.push(value),
+                        ArgumentValue::Many(values) => built.extend(values),
+                    }
+                }
+            }
+        }
+    }
+
+    built
+        .iter()
+        .map(|x| substitute(x, &all_substitutions))
+        .collect()
+}
+
+/// The placeholders whose substitutions are secret by default, which are
+/// redacted from the debug output of launch configurations and commands
+pub const DEFAULT_REDACTED_PLACEHOLDERS: &[&str] =
+    &["auth_access_token", "auth_session", "clientid", "auth_xuid"];
+
+#[derive(Clone)]
+/// The configuration of a launch of a version by [`VersionInfo::launch_command`]
+pub struct LaunchConfig {
+    /// The OS the game is launched on
+    pub os: Os,
+    /// The features toggled in the launcher
+    pub features: FeatureSet,
+    /// The working directory of the game, where its saves and options are
+    pub game_dir: PathBuf,
+    /// Where the libraries, assets, client jar and natives are installed
+    pub layout: Arc<dyn Layout>,
+    /// The path of the Java executable launching the game
+    pub java_path: PathBuf,
+    /// Replacements of the libraries of the version
+    pub overrides: LibraryOverrides,
+    /// The substitutions of the placeholders which can't be derived, see
+    /// [`VersionInfo::launch_command`]. They override the derived ones
+    pub substitutions: HashMap<String, String>,
+    /// The environment variables the game is launched with
+    pub environment: HashMap<String, String>,
+    /// The placeholders whose substitutions are secret, such as tokens of
+    /// the account. Their values are redacted from the debug output of the
+    /// configuration and of the launch command. Defaults to
+    /// [`DEFAULT_REDACTED_PLACEHOLDERS`]
+    pub redacted_placeholders: Vec<String>,
+}
+
+impl LaunchConfig {
+    /// Returns the substitutions of the redacted placeholders
+    fn secrets(&self) -> Vec<String> {
+        self.redacted_placeholders
+            .iter()
+            .filter_map(|x| self.substitutions.get(x))
+            .filter(|x| !x.is_empty())
+            .cloned()
+            .collect()
+    }
+}
+
+impl std::fmt::Debug for LaunchConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let secrets = self.secrets();
+
+        f.debug_struct("LaunchConfig")
+            .field("os", &self.os)
+            .field("features", &self.features)
+            .field("game_dir", &self.game_dir)
+            .field("layout", &self.layout)
+            .field("java_path", &self.java_path)
+            .field("overrides", &self.overrides)
+            .field(
+                "substitutions",
+                &self
+                    .substitutions
+                    .iter()
+                    .map(|(name, value)| (name, redact(value, &secrets)))
+                    .collect::<HashMap<_, _>>(),
+            )
+            .field("environment", &self.environment)
+            .field("redacted_placeholders", &self.redacted_placeholders)
+            .finish()
+    }
+}
+
+impl LaunchConfig {
+    /// Creates a configuration for the OS without any toggled feature, with
+    /// the game installed in the game directory in the layout of the vanilla
+    /// launcher
+    pub fn new(os: Os, game_dir: &Path, java_path: &Path) -> LaunchConfig {
+        LaunchConfig {
+            os,
+            features: FeatureSet::default(),
+            game_dir: game_dir.to_path_buf(),
+            layout: Arc::new(MojangLayout::new(game_dir)),
+            java_path: java_path.to_path_buf(),
+            overrides: LibraryOverrides::default(),
+            substitutions: HashMap::new(),
+            environment: HashMap::new(),
+            redacted_placeholders: DEFAULT_REDACTED_PLACEHOLDERS
+                .iter()
+                .map(|x| x.to_string())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq)]
+/// The command launching a version. Its debug output redacts the secrets in
+/// the arguments, so it can be logged
+pub struct LaunchCommand {
+    /// The Java executable to run
+    pub program: PathBuf,
+    /// The JVM arguments, the main class and the game arguments
+    pub args: Vec<String>,
+    /// The environment variables to set
+    pub environment: HashMap<String, String>,
+    /// The working directory to run the program in
+    pub current_dir: PathBuf,
+    /// The secret values redacted from the debug output, the substitutions of
+    /// the [`LaunchConfig::redacted_placeholders`]
+    pub secrets: Vec<String>,
+}
+
+impl std::fmt::Debug for LaunchCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LaunchCommand")
+            .field("program", &self.program)
+            .field(
+                "args",
+                &self
+                    .args
+                    .iter()
+                    .map(|x| redact(x, &self.secrets))
+                    .collect::<Vec<_>>(),
+            )
+            .field("environment", &self.environment)
+            .field("current_dir", &self.current_dir)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Replaces the secrets in the value
+fn redact(value: &str, secrets: &[String]) -> String {
+    secrets.iter().fold(value.to_string(), |value, x| {
+        value.replace(&**x, "<redacted>")
+    })
+}
+
+impl LaunchCommand {
+    /// Creates a process builder running the command
+    pub fn to_command(&self) -> std::process::Command {
+        let mut command = std::process::Command::new(&self.program);
+        command
+            .args(&self.args)
+            .envs(&self.environment)
+            .current_dir(&self.current_dir);
+
+        command
+    }
+}
+
+impl VersionInfo {
+    /// Builds the command launching the version with the configuration, with
+    /// the JVM arguments and the game arguments built by [`build_arguments`]
+    /// around the main class.
+    ///
+    /// Besides the [`platform_substitutions`], the `classpath` is derived with
+    /// [`classpath`] and the `game_directory` is the one of the
+    /// configuration. The configuration has to provide the rest, which for
+    /// the current versions are:
+    ///
+    /// - `auth_player_name`, `auth_uuid`, `auth_access_token`, `auth_xuid`,
+    ///   `clientid` and `user_type`, from the account of the player
+    /// - `launcher_name` and `launcher_version`, naming the launcher
+    /// - `resolution_width` and `resolution_height` when the custom
+    ///   resolution feature is toggled, and `quickPlayPath`,
+    ///   `quickPlaySingleplayer`, `quickPlayMultiplayer` and
+    ///   `quickPlayRealms` when the quick play features are
+    ///
+    /// Legacy versions also use `auth_session`, `user_properties` and
+    /// `game_assets`. Fails with [`Error::MissingSubstitution`] if an
+    /// argument references a placeholder without a substitution
+    pub fn launch_command(
+        &self,
+        config: &LaunchConfig,
+    ) -> Result<LaunchCommand, Error> {
+        let layout = &*config.layout;
+        let classpath = classpath(
+            self,
+            &config.os,
+            &config.features,
+            layout,
+            &config.overrides,
+        )?;
+
+        let mut substitutions = HashMap::new();
+        substitutions.insert(
+            "classpath".to_string(),
+            classpath
+                .iter()
+                .map(|x| x.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(classpath_separator(&config.os)),
+        );
+        substitutions.insert(
+            "game_directory".to_string(),
+            config.game_dir.to_string_lossy().into_owned(),
+        );
+        substitutions.extend(
+            config
+                .substitutions
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone())),
+        );
+
+        let build = |type_| {
+            build_arguments(
+                self,
+                type_,
+                &config.os,
+                &config.features,
+                layout,
+                &substitutions,
+            )
+        };
+
+        let mut args = build(ArgumentType::Jvm);
+        args.push(self.main_class.clone());
+        args.extend(build(ArgumentType::Game));
+
+        if let Some(name) = args.iter().find_map(|x| unsubstituted(x)) {
+            return Err(Error::MissingSubstitution {
+                name: name.to_string(),
+            });
+        }
+
+        Ok(LaunchCommand {
+            program: config.java_path.clone(),
+            args,
+            environment: config.environment.clone(),
+            current_dir: config.game_dir.clone(),
+            secrets: config.secrets(),
+        })
+    }
+}
+
+/// Returns the name of the first placeholder left in the argument
+fn unsubstituted(argument: &str) -> Option<&str> {
+    let start = argument.find("${")?;
+    let rest = &argument[start + 2..];
+
+    rest.find('}').map(|end| &rest[..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version() -> VersionInfo {
+        serde_json::from_slice(
+            &std::fs::read(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/fixtures/1.19.json"
+            ))
+            .unwrap(),
+        )
+        .unwrap()
+    }
+
+    /// A version splitting its game arguments from the legacy
+    /// `minecraftArguments` string
+    fn legacy_version() -> VersionInfo {
+        let mut info = version();
+        info.arguments = None;
+        info.minecraft_arguments = Some(
+            "--username ${auth_player_name} --session ${auth_session} \
+             --gameDir ${game_directory}"
+                .to_string(),
+        );
+
+        info
+    }
+
+    fn config() -> LaunchConfig {
+        let mut config = LaunchConfig::new(
+            Os::Linux,
+            Path::new("/games/minecraft"),
+            Path::new("/usr/bin/java"),
+        );
+        config.substitutions = [
+            ("auth_player_name", "Steve"),
+            ("auth_uuid", "069a79f444e94726a5befca90e38aaf5"),
+            ("auth_access_token", "secret-token"),
+            ("auth_session", "token:secret-token"),
+            ("launcher_name", "daedalus"),
+            ("launcher_version", "1.0"),
+        ]
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+
+        config
+    }
+
+    fn path(path: PathBuf) -> String {
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn classpath_lists_the_libraries_then_the_client_jar() {
+        let layout = MojangLayout::new(Path::new("/games/minecraft"));
+        let classpath = classpath(
+            &version(),
+            &Os::Linux,
+            &FeatureSet::default(),
+            &layout,
+            &LibraryOverrides::default(),
+        )
+        .unwrap();
+
+        let libraries = layout.libraries_dir();
+        assert_eq!(
+            classpath,
+            vec![
+                libraries.join("com/mojang/logging/1.0.0/logging-1.0.0.jar"),
+                libraries.join("org/lwjgl/lwjgl/3.3.1/lwjgl-3.3.1.jar"),
+                libraries.join(
+                    "org/lwjgl/lwjgl/3.3.1/lwjgl-3.3.1-natives-linux.jar"
+                ),
+                layout.client_jar_path("1.19"),
+            ]
+        );
+    }
+
+    #[test]
+    fn launch_command_substitutes_every_argument() {
+        let config = config();
+        let command = version().launch_command(&config).unwrap();
+
+        let layout = &*config.layout;
+        let classpath = classpath(
+            &version(),
+            &Os::Linux,
+            &FeatureSet::default(),
+            layout,
+            &LibraryOverrides::default(),
+        )
+        .unwrap()
+        .into_iter()
+        .map(path)
+        .collect::<Vec<_>>()
+        .join(":");
+        let mut args = Vec::new();
+        if cfg!(target_arch = "x86") {
+            args.push("-Xss1M".to_string());
+        }
+        args.extend(
+            [
+                format!(
+                    "-Djava.library.path={}",
+                    path(layout.natives_dir("1.19"))
+                ),
+                "-Dminecraft.launcher.brand=daedalus".to_string(),
+                "-Dminecraft.launcher.version=1.0".to_string(),
+                "-cp".to_string(),
+                classpath,
+                "net.minecraft.client.main.Main".to_string(),
+                "--username".to_string(),
+                "Steve".to_string(),
+                "--version".to_string(),
+                "1.19".to_string(),
+                "--gameDir".to_string(),
+                "/games/minecraft".to_string(),
+                "--assetsDir".to_string(),
+                path(layout.assets_dir()),
+                "--assetIndex".to_string(),
+                "1.19".to_string(),
+                "--uuid".to_string(),
+                "069a79f444e94726a5befca90e38aaf5".to_string(),
+                "--accessToken".to_string(),
+                "secret-token".to_string(),
+                "--versionType".to_string(),
+                "release".to_string(),
+            ]
+            .iter()
+            .cloned(),
+        );
+
+        assert_eq!(command.program, Path::new("/usr/bin/java"));
+        assert_eq!(command.args, args);
+        assert_eq!(command.current_dir, Path::new("/games/minecraft"));
+    }
+
+    #[test]
+    fn legacy_version_splits_its_game_arguments() {
+        let config = config();
+        let command = legacy_version().launch_command(&config).unwrap();
+
+        let main_class = command
+            .args
+            .iter()
+            .position(|x| x == "net.minecraft.client.main.Main")
+            .unwrap();
+        assert_eq!(command.args[main_class - 2], "-cp");
+        assert_eq!(
+            command.args[main_class + 1..],
+            [
+                "--username",
+                "Steve",
+                "--session",
+                "token:secret-token",
+                "--gameDir",
+                "/games/minecraft",
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_substitution_fails_the_launch() {
+        let mut config = config();
+        config.substitutions.remove("auth_uuid");
+
+        assert!(matches!(
+            version().launch_command(&config),
+            Err(Error::MissingSubstitution { name }) if name == "auth_uuid"
+        ));
+
+        config.substitutions.remove("auth_session");
+        assert!(matches!(
+            legacy_version().launch_command(&config),
+            Err(Error::MissingSubstitution { name }) if name == "auth_session"
+        ));
+    }
+
+    #[test]
+    fn debug_output_redacts_the_access_token() {
+        let config = config();
+        let command = version().launch_command(&config).unwrap();
+
+        for debug in [format!("{:?}", config), format!("{:?}", command)] {
+            assert!(!debug.contains("secret-token"));
+            assert!(debug.contains("<redacted>"));
+        }
+        assert!(command.args.iter().any(|x| x == "secret-token"));
+    }
+
+    #[test]
+    fn substitute_keeps_unknown_placeholders() {
+        let substitutions = [("version_name", "1.19")]
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+
+        assert_eq!(
+            substitute("${version_name}-${unknown}-${open", &substitutions),
+            "1.19-${unknown}-${open"
+        );
+    }
+}
@@ -0,0 +1,90 @@
+use crate::get_path_from_artifact;
+use crate::minecraft::VersionInfo;
+use crate::Error;
+use std::path::{Path, PathBuf};
+
+/// Describes where the files of a game installation are stored. The provided
+/// methods place the files in the directories the same way the vanilla
+/// launcher does, override them for other conventions
+pub trait Layout: std::fmt::Debug + Send + Sync {
+    /// The directory libraries are stored in, in the maven repository layout
+    fn libraries_dir(&self) -> PathBuf;
+    /// The directory assets and asset indexes are stored in
+    fn assets_dir(&self) -> PathBuf;
+    /// The directory the client jars and version infos are stored in
+    fn versions_dir(&self) -> PathBuf;
+
+    /// The path of a library from its maven artifact
+    fn library_path(&self, artifact: &str) -> Result<PathBuf, Error> {
+        Ok(self.libraries_dir().join(get_path_from_artifact(artifact)?))
+    }
+
+    /// The path of the asset index of a version
+    fn asset_index_path(&self, info: &VersionInfo) -> PathBuf {
+        info.asset_index_path(&self.assets_dir())
+    }
+
+    /// The path of an asset object from its hash
+    fn asset_object_path(&self, hash: &str) -> PathBuf {
+        self.assets_dir()
+            .join("objects")
+            .join(hash.get(..2).unwrap_or(hash))
+            .join(hash)
+    }
+
+    /// The path an asset is copied to by its name for asset indexes with the
+    /// [`AssetLayout::Legacy`](crate::minecraft::AssetLayout::Legacy) layout
+    fn virtual_asset_path(&self, index_id: &str, name: &str) -> PathBuf {
+        self.assets_dir().join("virtual").join(index_id).join(name)
+    }
+
+    /// The path of the client jar of a version
+    fn client_jar_path(&self, version_id: &str) -> PathBuf {
+        self.versions_dir()
+            .join(version_id)
+            .join(format!("{}.jar", version_id))
+    }
+
+    /// The path of the version info of a version
+    fn version_info_path(&self, version_id: &str) -> PathBuf {
+        self.versions_dir()
+            .join(version_id)
+            .join(format!("{}.json", version_id))
+    }
+
+    /// The directory the natives of a version are extracted to
+    fn natives_dir(&self, version_id: &str) -> PathBuf {
+        self.versions_dir().join(version_id).join("natives")
+    }
+}
+
+/// The layout of the vanilla launcher, with the `libraries`, `assets` and
+/// `versions` directories in a common root directory
+#[derive(Debug, Clone)]
+pub struct MojangLayout {
+    /// The root directory of the installation
+    pub root: PathBuf,
+}
+
+impl MojangLayout {
+    /// Creates a layout with the root directory
+    pub fn new(root: &Path) -> MojangLayout {
+        MojangLayout {
+            root: root.to_path_buf(),
+        }
+    }
+}
+
+impl Layout for MojangLayout {
+    fn libraries_dir(&self) -> PathBuf {
+        self.root.join("libraries")
+    }
+
+    fn assets_dir(&self) -> PathBuf {
+        self.root.join("assets")
+    }
+
+    fn versions_dir(&self) -> PathBuf {
+        self.root.join("versions")
+    }
+}
@@ -4,22 +4,235 @@
 
 #![warn(missing_docs, unused_import_braces, missing_debug_implementations)]
 
+use download::{DownloadKind, Downloader, DOWNLOADER};
 use once_cell::sync::OnceCell;
 
+/// Configurable downloading of files, used by all fetch functions
+pub mod download;
+/// Building the command line to launch a version
+pub mod launch;
+/// Directory layouts of game installations
+pub mod layout;
 /// Models and methods for fetching metadata for Minecraft
 pub mod minecraft;
 /// Models and methods for fetching metadata for Minecraft mod loaders
 pub mod modded;
+/// Models and methods for fetching the patch notes of Minecraft versions
+#[cfg(feature = "patch_notes")]
+pub mod patch_notes;
+/// Pluggable sources of the game metadata
+pub mod source;
+/// Verification of installed game files
+pub mod verify;
 
 /// Your branding, used for the user agent and similar
 #[derive(Debug)]
 pub struct Branding {
     /// The name of your application
     pub header_value: String,
-    /// The string to replace in the name of the application
+    /// A placeholder for the game version, `${<name>.gameVersion}`. Versions
+    /// shared by all game versions, such as those of mod loaders, use it in
+    /// their IDs and library names and URLs, which [`apply_game_version`]
+    /// replaces with the actual game version
     pub dummy_replace_string: String,
 }
 
+/// The map of the model structs. With the `preserve_order` feature it keeps
+/// the order its entries were deserialized in, so documents serialized again
+/// diff cleanly against the originals
+#[cfg(feature = "preserve_order")]
+pub type Map<K, V> = OrderedMap<K, V>;
+/// The map of the model structs. With the `preserve_order` feature it keeps
+/// the order its entries were deserialized in, so documents serialized again
+/// diff cleanly against the originals
+#[cfg(not(feature = "preserve_order"))]
+pub type Map<K, V> = std::collections::HashMap<K, V>;
+
+#[cfg(feature = "preserve_order")]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[serde(
+    transparent,
+    bound(deserialize = "K: serde::Deserialize<'de> + Eq + std::hash::Hash, \
+                         V: serde::Deserialize<'de>")
+)]
+/// A map which keeps the order its entries were inserted in, the [`Map`] of
+/// the model structs with the `preserve_order` feature. It derefs to an
+/// [`indexmap::IndexMap`], and is encoded with bincode like a `HashMap`
+pub struct OrderedMap<K, V>(pub indexmap::IndexMap<K, V>);
+
+#[cfg(feature = "preserve_order")]
+impl<K, V> OrderedMap<K, V> {
+    /// Creates an empty map
+    pub fn new() -> OrderedMap<K, V> {
+        OrderedMap(indexmap::IndexMap::new())
+    }
+}
+
+#[cfg(feature = "preserve_order")]
+impl<K, V> Default for OrderedMap<K, V> {
+    fn default() -> Self {
+        OrderedMap::new()
+    }
+}
+
+#[cfg(feature = "preserve_order")]
+impl<K: Eq + std::hash::Hash, V: PartialEq> PartialEq for OrderedMap<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+#[cfg(feature = "preserve_order")]
+impl<K: Eq + std::hash::Hash, V: Eq> Eq for OrderedMap<K, V> {}
+
+#[cfg(feature = "preserve_order")]
+impl<K, V> std::ops::Deref for OrderedMap<K, V> {
+    type Target = indexmap::IndexMap<K, V>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[cfg(feature = "preserve_order")]
+impl<K, V> std::ops::DerefMut for OrderedMap<K, V> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[cfg(feature = "preserve_order")]
+impl<K: Eq + std::hash::Hash, V> std::iter::FromIterator<(K, V)>
+    for OrderedMap<K, V>
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        OrderedMap(iter.into_iter().collect())
+    }
+}
+
+#[cfg(feature = "preserve_order")]
+impl<K: Eq + std::hash::Hash, V> Extend<(K, V)> for OrderedMap<K, V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        self.0.extend(iter)
+    }
+}
+
+#[cfg(feature = "preserve_order")]
+impl<K, V> IntoIterator for OrderedMap<K, V> {
+    type Item = (K, V);
+    type IntoIter = indexmap::map::IntoIter<K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+#[cfg(feature = "preserve_order")]
+impl<'a, K, V> IntoIterator for &'a OrderedMap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = indexmap::map::Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+// The entries are encoded as a sequence of pairs, which is how bincode
+// encodes a `HashMap` too
+#[cfg(all(feature = "preserve_order", feature = "bincode"))]
+impl<K: bincode::Encode, V: bincode::Encode> bincode::Encode
+    for OrderedMap<K, V>
+{
+    fn encode<E: bincode::enc::Encoder>(
+        &self,
+        encoder: &mut E,
+    ) -> Result<(), bincode::error::EncodeError> {
+        self.0.iter().collect::<Vec<_>>().encode(encoder)
+    }
+}
+
+#[cfg(all(feature = "preserve_order", feature = "bincode"))]
+impl<Context, K, V> bincode::Decode<Context> for OrderedMap<K, V>
+where
+    K: bincode::Decode<Context> + Eq + std::hash::Hash,
+    V: bincode::Decode<Context>,
+{
+    fn decode<D: bincode::de::Decoder<Context = Context>>(
+        decoder: &mut D,
+    ) -> Result<Self, bincode::error::DecodeError> {
+        Ok(Vec::<(K, V)>::decode(decoder)?.into_iter().collect())
+    }
+}
+
+#[cfg(all(feature = "preserve_order", feature = "bincode"))]
+impl<'de, Context, K, V> bincode::BorrowDecode<'de, Context>
+    for OrderedMap<K, V>
+where
+    K: bincode::BorrowDecode<'de, Context> + Eq + std::hash::Hash,
+    V: bincode::BorrowDecode<'de, Context>,
+{
+    fn borrow_decode<D: bincode::de::BorrowDecoder<'de, Context = Context>>(
+        decoder: &mut D,
+    ) -> Result<Self, bincode::error::DecodeError> {
+        Ok(Vec::<(K, V)>::borrow_decode(decoder)?.into_iter().collect())
+    }
+}
+
+#[derive(
+    serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq, Eq,
+)]
+#[serde(transparent)]
+/// The fields of a document which aren't modelled, such as `_comment_`, by
+/// their name, preserved so they are serialized again. Bincode can't decode
+/// arbitrary JSON values, so they are encoded with bincode as a JSON string
+pub struct UnknownFields(pub Map<String, serde_json::Value>);
+
+impl std::ops::Deref for UnknownFields {
+    type Target = Map<String, serde_json::Value>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl std::ops::DerefMut for UnknownFields {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[cfg(feature = "bincode")]
+impl bincode::Encode for UnknownFields {
+    fn encode<E: bincode::enc::Encoder>(
+        &self,
+        encoder: &mut E,
+    ) -> Result<(), bincode::error::EncodeError> {
+        serde_json::to_string(&self.0)
+            .map_err(|err| {
+                bincode::error::EncodeError::OtherString(err.to_string())
+            })?
+            .encode(encoder)
+    }
+}
+
+#[cfg(feature = "bincode")]
+impl<Context> bincode::Decode<Context> for UnknownFields {
+    fn decode<D: bincode::de::Decoder<Context = Context>>(
+        decoder: &mut D,
+    ) -> Result<Self, bincode::error::DecodeError> {
+        let json = String::decode(decoder)?;
+
+        serde_json::from_str(&json)
+            .map(UnknownFields)
+            .map_err(|err| {
+                bincode::error::DecodeError::OtherString(err.to_string())
+            })
+    }
+}
+
+#[cfg(feature = "bincode")]
+bincode::impl_borrow_decode!(UnknownFields);
+
 /// The branding of your application
 pub static BRANDING: OnceCell<Branding> = OnceCell::new();
 
@@ -40,6 +253,17 @@ impl Branding {
         }
     }
 
+    /// Creates a new branding instance sending the user agent as is, instead
+    /// of the default format of [`Branding::new`]
+    pub fn with_user_agent(name: String, user_agent: String) -> Branding {
+        let dummy_replace_string = format!("${{{}.gameVersion}}", name);
+
+        Branding {
+            header_value: user_agent,
+            dummy_replace_string,
+        }
+    }
+
     /// Returns the branding instance
     pub fn set_branding(branding: Branding) -> Result<(), Error> {
         BRANDING
@@ -70,6 +294,16 @@ pub enum Error {
     /// There was an error while deserializing metadata
     #[error("Error while deserializing JSON")]
     SerdeError(#[from] serde_json::Error),
+    /// A fetched document could not be deserialized into its model type
+    #[error("Failed to deserialize {type_name} from {url}: {source}")]
+    DeserializeContext {
+        /// The URL the document was fetched from
+        url: String,
+        /// The name of the model type the document was deserialized into
+        type_name: &'static str,
+        /// The internal serde error
+        source: serde_json::Error,
+    },
     /// There was a network error when fetching an object
     #[error("Unable to fetch {item}")]
     FetchError {
@@ -81,99 +315,404 @@ pub enum Error {
     /// There was an error when managing async tasks
     #[error("Error while managing asynchronous tasks")]
     TaskError(#[from] tokio::task::JoinError),
+    /// There was an error while waiting for a download slot
+    #[error("Error acquiring semaphore: {0}")]
+    AcquireError(#[from] tokio::sync::AcquireError),
     /// Error while parsing input
     #[error("{0}")]
     ParseError(String),
+    /// There was an error while reading or writing a file
+    #[error("Error while reading or writing a file: {0}")]
+    IoError(#[from] std::io::Error),
+    /// There was an error while encoding a model with bincode
+    #[cfg(feature = "bincode")]
+    #[error("Error while encoding with bincode: {0}")]
+    BincodeEncodeError(#[from] bincode::error::EncodeError),
+    /// There was an error while decoding a model with bincode
+    #[cfg(feature = "bincode")]
+    #[error("Error while decoding with bincode: {0}")]
+    BincodeDecodeError(#[from] bincode::error::DecodeError),
+    /// There was an error while reading a zip archive
+    #[cfg(feature = "zip")]
+    #[error("Error while reading a zip archive: {0}")]
+    ZipError(#[from] zip::result::ZipError),
     /// The branding has already been set
     #[error("Branding already set")]
     BrandingAlreadySet,
+    /// The downloader has already been set
+    #[error("Downloader already set")]
+    DownloaderAlreadySet,
+    /// A download did not finish within its overall timeout
+    #[error("Timed out fetching {url} after {elapsed:?}")]
+    Timeout {
+        /// The URL of the file attempted to be downloaded
+        url: String,
+        /// The time spent on the download before giving up
+        elapsed: std::time::Duration,
+    },
+    /// A downloaded file is larger than it may be, see
+    /// [`Downloader::max_size`](download::Downloader::max_size)
+    #[error("Response from {url} exceeds the limit of {limit} bytes")]
+    ResponseTooLarge {
+        /// The URL of the file attempted to be downloaded
+        url: String,
+        /// The most bytes the file may have
+        limit: u64,
+    },
+    /// An operation was cancelled through its cancellation token
+    #[error("Operation cancelled")]
+    Cancelled,
+    /// A version could not be found in the version manifest
+    #[error("Version {id} not found in the version manifest")]
+    VersionNotFound {
+        /// The ID of the version which was looked up
+        id: String,
+    },
+    /// A library override replaces no library of the version without being
+    /// additive
+    #[error("Library override {coordinate} matches no library of the version")]
+    UnmatchedLibraryOverride {
+        /// The `groupId:artifactId` of the override
+        coordinate: String,
+    },
+    /// An argument of a launch command references a placeholder without a
+    /// substitution
+    #[error("No substitution for the placeholder {name}")]
+    MissingSubstitution {
+        /// The name of the placeholder
+        name: String,
+    },
+    /// A library declares natives for a classifier it has no download for
+    #[error("Library {library} has no download for its natives {classifier}")]
+    MissingNativeDownload {
+        /// The maven name of the library
+        library: String,
+        /// The classifier of the natives
+        classifier: String,
+    },
+    /// A fetched document uses a newer format than this crate supports
+    #[error("Unsupported format version {found}, only up to {supported} is supported")]
+    UnsupportedFormatVersion {
+        /// The format version of the document
+        found: usize,
+        /// The latest format version supported by this crate
+        supported: usize,
+    },
+    /// A document contains a field its model type doesn't know of, only
+    /// returned by the strict parsing functions
+    #[error("Unknown field {field} in {type_name}")]
+    UnknownField {
+        /// The path of the field, with the keys and indexes leading to it
+        /// separated by dots
+        field: String,
+        /// The name of the model type the document was deserialized into
+        type_name: &'static str,
+    },
     /// Invalid Minecraft Java Profile
     #[error("Invalid Minecraft Java Profile")]
     InvalidMinecraftJavaProfile(String),
+    /// The version requires a newer launcher than the one supported
+    #[error("Version requires launcher version {required}, but only {supported} is supported")]
+    UnsupportedLauncherVersion {
+        /// The minimum launcher version required by the version
+        required: u32,
+        /// The launcher version supported by the caller
+        supported: u32,
+    },
 }
 
-/// Converts a maven artifact to a path
-pub fn get_path_from_artifact(artifact: &str) -> Result<String, Error> {
-    let name_items = artifact.split(':').collect::<Vec<&str>>();
+impl Error {
+    /// Whether the error may be transient, so the failed operation may succeed
+    /// when it is retried. Errors of the network and of file operations are
+    /// retryable when they are timeouts, interrupted connections or server
+    /// errors, but not when the request was rejected, such as with a 404.
+    /// Checksum failures aren't retryable, as the download was already retried.
+    /// Task errors are only retryable if the task was cancelled instead of
+    /// panicking. Invalid documents, configuration errors and versions which
+    /// aren't supported can't be fixed by retrying
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::FetchError { inner, .. } => match inner.status() {
+                Some(status) => {
+                    status.is_server_error()
+                        || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                }
+                None => {
+                    inner.is_timeout()
+                        || inner.is_connect()
+                        || inner.is_request()
+                        || inner.is_body()
+                }
+            },
+            Error::Timeout { .. } => true,
+            Error::IoError(err) => matches!(
+                err.kind(),
+                std::io::ErrorKind::Interrupted
+                    | std::io::ErrorKind::TimedOut
+                    | std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+                    | std::io::ErrorKind::UnexpectedEof
+            ),
+            Error::TaskError(err) => err.is_cancelled(),
+            #[cfg(feature = "zip")]
+            Error::ZipError(_) => false,
+            #[cfg(feature = "bincode")]
+            Error::BincodeEncodeError(_) | Error::BincodeDecodeError(_) => {
+                false
+            }
+            Error::ChecksumFailure { .. }
+            | Error::SerdeError(_)
+            | Error::DeserializeContext { .. }
+            | Error::AcquireError(_)
+            | Error::ParseError(_)
+            | Error::BrandingAlreadySet
+            | Error::DownloaderAlreadySet
+            | Error::Cancelled
+            | Error::ResponseTooLarge { .. }
+            | Error::VersionNotFound { .. }
+            | Error::UnmatchedLibraryOverride { .. }
+            | Error::MissingSubstitution { .. }
+            | Error::MissingNativeDownload { .. }
+            | Error::UnsupportedFormatVersion { .. }
+            | Error::UnknownField { .. }
+            | Error::InvalidMinecraftJavaProfile(_)
+            | Error::UnsupportedLauncherVersion { .. } => false,
+        }
+    }
+}
 
-    let package = name_items.first().ok_or_else(|| {
-        Error::ParseError(format!(
-            "Unable to find package for library {}",
+/// Returns a function wrapping a reqwest error into an [`Error::FetchError`]
+/// for the URL, for use with `map_err`
+pub fn fetch_err(url: &str) -> impl FnOnce(reqwest::Error) -> Error + '_ {
+    move |inner| Error::FetchError {
+        inner,
+        item: url.to_string(),
+    }
+}
+
+/// Replaces the placeholder for the game version of the branding in the input
+/// with the game version, see [`Branding::dummy_replace_string`]
+pub fn apply_game_version(input: &str, game_version: &str) -> String {
+    input.replace(
+        &BRANDING.get_or_init(Branding::default).dummy_replace_string,
+        game_version,
+    )
+}
+
+/// Splits the extension from the last part of a maven artifact at `@`,
+/// defaulting to `jar`
+fn split_extension<'a>(
+    artifact: &str,
+    part: &'a str,
+) -> Result<(&'a str, &'a str), Error> {
+    let mut parts = part.split('@');
+    let base = parts.next().unwrap_or_default();
+
+    match (parts.next(), parts.next()) {
+        (None, _) => Ok((base, "jar")),
+        (Some(ext), None) if !ext.is_empty() => Ok((base, ext)),
+        _ => Err(Error::ParseError(format!(
+            "Invalid extension for library {}",
             &artifact
-        ))
-    })?;
-    let name = name_items.get(1).ok_or_else(|| {
+        ))),
+    }
+}
+
+/// Checks that the parts of a maven artifact can't make its path escape the
+/// directory it is joined to: no part may be empty or contain `..`, a path
+/// separator or a null byte, and the group, whose dots become path
+/// separators, may not start or end with a dot
+fn check_artifact_parts(
+    artifact: &str,
+    parts: &[&str],
+    classifier: Option<&str>,
+) -> Result<(), Error> {
+    let unsafe_part = |x: &str| {
+        x.is_empty() || x.contains("..") || x.contains(['/', '\\', '\0'])
+    };
+    let group = parts.first().copied().unwrap_or_default();
+
+    if group.split('.').any(|x| x.is_empty())
+        || parts.iter().copied().chain(classifier).any(unsafe_part)
+    {
+        return Err(Error::ParseError(format!(
+            "Unsafe path in library {}",
+            artifact
+        )));
+    }
+
+    Ok(())
+}
+
+/// Converts a maven artifact to a path. An `@ext` suffix, such as `@zip` or
+/// `@tar.gz`, replaces the default `jar` extension.
+/// Fails for artifacts whose path could escape the directory it is joined to,
+/// such as through `..` or path separators in their parts
+pub fn get_path_from_artifact(artifact: &str) -> Result<String, Error> {
+    let missing = |part: &str| {
         Error::ParseError(format!(
-            "Unable to find name for library {}",
-            &artifact
+            "Unable to find {} for library {}",
+            part, &artifact
         ))
-    })?;
-
-    if name_items.len() == 3 {
-        let version_ext = name_items
-            .get(2)
-            .ok_or_else(|| {
-                Error::ParseError(format!(
-                    "Unable to find version for library {}",
-                    &artifact
-                ))
-            })?
-            .split('@')
-            .collect::<Vec<&str>>();
-        let version = version_ext.first().ok_or_else(|| {
-            Error::ParseError(format!(
-                "Unable to find version for library {}",
-                &artifact
-            ))
-        })?;
-        let ext = version_ext.get(1);
+    };
 
-        Ok(format!(
-            "{}/{}/{}/{}-{}.{}",
-            package.replace('.', "/"),
-            name,
-            version,
-            name,
-            version,
-            ext.unwrap_or(&"jar")
-        ))
-    } else {
-        let version = name_items.get(2).ok_or_else(|| {
-            Error::ParseError(format!(
-                "Unable to find version for library {}",
-                &artifact
-            ))
-        })?;
-
-        let data_ext = name_items
-            .get(3)
-            .ok_or_else(|| {
-                Error::ParseError(format!(
-                    "Unable to find data for library {}",
-                    &artifact
-                ))
-            })?
-            .split('@')
-            .collect::<Vec<&str>>();
-        let data = data_ext.first().ok_or_else(|| {
-            Error::ParseError(format!(
-                "Unable to find data for library {}",
-                &artifact
-            ))
-        })?;
-        let ext = data_ext.get(1);
+    let mut items = artifact.split(':');
+    let package = items.next().unwrap_or_default();
+    let name = items.next().ok_or_else(|| missing("name"))?;
+    let version = items.next();
+    let data = items.next();
 
-        Ok(format!(
-            "{}/{}/{}/{}-{}-{}.{}",
-            package.replace('.', "/"),
-            name,
-            version,
-            name,
-            version,
-            data,
-            ext.unwrap_or(&"jar")
-        ))
+    let (version, data, ext) = match (version, data) {
+        (Some(version), None) => {
+            let (version, ext) = split_extension(artifact, version)?;
+            (version, None, ext)
+        }
+        (Some(version), Some(data)) => {
+            let (data, ext) = split_extension(artifact, data)?;
+            (version, Some(data), ext)
+        }
+        (None, _) => return Err(missing("version")),
+    };
+
+    check_artifact_parts(artifact, &[package, name, version, ext], data)?;
+
+    let mut path = String::with_capacity(
+        package.len()
+            + 2 * (name.len() + version.len())
+            + data.map_or(0, |x| x.len() + 1)
+            + ext.len()
+            + 5,
+    );
+    path.extend(package.chars().map(|x| if x == '.' { '/' } else { x }));
+    for part in [name, version] {
+        path.push('/');
+        path.push_str(part);
     }
+    path.push('/');
+    path.push_str(name);
+    path.push('-');
+    path.push_str(version);
+    if let Some(data) = data {
+        path.push('-');
+        path.push_str(data);
+    }
+    path.push('.');
+    path.push_str(ext);
+
+    Ok(path)
+}
+
+/// A parsed maven artifact, written as
+/// `groupId:artifactId:version[:classifier][@extension]`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MavenCoordinate {
+    /// The group ID of the artifact
+    pub group: String,
+    /// The artifact ID of the artifact
+    pub artifact: String,
+    /// The version of the artifact
+    pub version: String,
+    /// The classifier of the artifact, for example `natives-linux`
+    pub classifier: Option<String>,
+    /// The file extension of the artifact, `jar` unless it is specified
+    pub extension: String,
+}
+
+impl MavenCoordinate {
+    /// The path of the artifact in a maven repository, as returned by
+    /// [`get_path_from_artifact`]
+    pub fn path(&self) -> String {
+        let classifier = self
+            .classifier
+            .as_ref()
+            .map(|x| format!("-{}", x))
+            .unwrap_or_default();
+
+        format!(
+            "{}/{}/{}/{}-{}{}.{}",
+            self.group.replace('.', "/"),
+            self.artifact,
+            self.version,
+            self.artifact,
+            self.version,
+            classifier,
+            self.extension
+        )
+    }
+}
+
+impl std::str::FromStr for MavenCoordinate {
+    type Err = Error;
+
+    fn from_str(artifact: &str) -> Result<MavenCoordinate, Error> {
+        let (coordinate, extension) = split_extension(artifact, artifact)?;
+
+        let invalid = || {
+            Error::ParseError(format!("Invalid maven coordinate {}", artifact))
+        };
+
+        let (group, name, version, classifier) =
+            match coordinate.split(':').collect::<Vec<&str>>()[..] {
+                [group, name, version] => (group, name, version, None),
+                [group, name, version, classifier] => {
+                    (group, name, version, Some(classifier))
+                }
+                _ => return Err(invalid()),
+            };
+
+        if [group, name, version].iter().any(|x| x.is_empty())
+            || classifier.is_some_and(|x| x.is_empty())
+        {
+            return Err(invalid());
+        }
+
+        check_artifact_parts(
+            artifact,
+            &[group, name, version, extension],
+            classifier,
+        )?;
+
+        Ok(MavenCoordinate {
+            group: group.to_string(),
+            artifact: name.to_string(),
+            version: version.to_string(),
+            classifier: classifier.map(|x| x.to_string()),
+            extension: extension.to_string(),
+        })
+    }
+}
+
+impl std::fmt::Display for MavenCoordinate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.group, self.artifact, self.version)?;
+        if let Some(classifier) = &self.classifier {
+            write!(f, ":{}", classifier)?;
+        }
+        if self.extension != "jar" {
+            write!(f, "@{}", self.extension)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Strips the classifier and extension from a maven artifact, returning
+/// `groupId:artifactId:version`
+pub fn maven_base_coordinate(artifact: &str) -> Result<String, Error> {
+    let coordinate: MavenCoordinate = artifact.parse()?;
+
+    Ok(format!(
+        "{}:{}:{}",
+        coordinate.group, coordinate.artifact, coordinate.version
+    ))
+}
+
+/// Strips the version, classifier and extension from a maven artifact,
+/// returning `groupId:artifactId`
+pub fn maven_ga(artifact: &str) -> Result<String, Error> {
+    let coordinate: MavenCoordinate = artifact.parse()?;
+
+    Ok(format!("{}:{}", coordinate.group, coordinate.artifact))
 }
 
 /// Downloads a file from specified mirrors
@@ -182,85 +721,372 @@ pub async fn download_file_mirrors(
     mirrors: &[&str],
     sha1: Option<&str>,
 ) -> Result<bytes::Bytes, Error> {
-    if mirrors.is_empty() {
-        return Err(Error::ParseError("No mirrors provided!".to_string()));
-    }
+    DOWNLOADER
+        .get_or_init(Downloader::default)
+        .download_file_mirrors(base, mirrors, sha1)
+        .await
+}
 
-    for (index, mirror) in mirrors.iter().enumerate() {
-        let result = download_file(&format!("{}{}", mirror, base), sha1).await;
+/// Sorts the mirrors by their latency, fastest first
+pub async fn rank_mirrors(mirrors: &[&str]) -> Vec<String> {
+    DOWNLOADER
+        .get_or_init(Downloader::default)
+        .rank_mirrors(mirrors)
+        .await
+}
 
-        if result.is_ok() || (result.is_err() && index == (mirrors.len() - 1)) {
-            return result;
-        }
-    }
+/// Downloads a file with retry and checksum functionality, applying the
+/// timeout of artifacts, see [`Downloader::artifact_timeout`]
+pub async fn download_file(
+    url: &str,
+    sha1: Option<&str>,
+) -> Result<bytes::Bytes, Error> {
+    DOWNLOADER
+        .get_or_init(Downloader::default)
+        .download_file(url, sha1)
+        .await
+}
 
-    unreachable!()
+/// Downloads a file with retry and checksum functionality, failing if it is
+/// larger than its expected size
+pub async fn download_file_with_size(
+    url: &str,
+    sha1: Option<&str>,
+    size: Option<u64>,
+) -> Result<bytes::Bytes, Error> {
+    DOWNLOADER
+        .get_or_init(Downloader::default)
+        .download_file_with_size(url, sha1, size)
+        .await
 }
 
-/// Downloads a file with retry and checksum functionality
-pub async fn download_file(
+/// Downloads a file with retry and checksum functionality, applying the
+/// timeout of the kind of file
+pub async fn download_file_with_kind(
+    url: &str,
+    sha1: Option<&str>,
+    kind: DownloadKind,
+) -> Result<bytes::Bytes, Error> {
+    DOWNLOADER
+        .get_or_init(Downloader::default)
+        .download_file_with_kind(url, sha1, kind)
+        .await
+}
+
+/// Downloads a metadata document, used by the fetch functions
+pub(crate) async fn download_metadata(
+    url: &str,
+    sha1: Option<&str>,
+) -> Result<bytes::Bytes, Error> {
+    download_metadata_with_size(url, sha1, None).await
+}
+
+/// Downloads a metadata document of a known size, such as an assets index
+pub(crate) async fn download_metadata_with_size(
     url: &str,
     sha1: Option<&str>,
+    size: Option<u64>,
 ) -> Result<bytes::Bytes, Error> {
-    let mut headers = reqwest::header::HeaderMap::new();
-    if let Ok(header) = reqwest::header::HeaderValue::from_str(
-        &BRANDING.get_or_init(Branding::default).header_value,
-    ) {
-        headers.insert(reqwest::header::USER_AGENT, header);
-    }
-    let client = reqwest::Client::builder()
-        .tcp_keepalive(Some(std::time::Duration::from_secs(10)))
-        .timeout(std::time::Duration::from_secs(15))
-        .default_headers(headers)
-        .build()
-        .map_err(|err| Error::FetchError {
-            inner: err,
-            item: url.to_string(),
-        })?;
-
-    for attempt in 1..=4 {
-        let result = client.get(url).send().await;
-
-        match result {
-            Ok(x) => {
-                let bytes = x.bytes().await;
-
-                if let Ok(bytes) = bytes {
-                    if let Some(sha1) = sha1 {
-                        if &*get_hash(bytes.clone()).await? != sha1 {
-                            if attempt <= 3 {
-                                continue;
-                            } else {
-                                return Err(Error::ChecksumFailure {
-                                    hash: sha1.to_string(),
-                                    url: url.to_string(),
-                                    tries: attempt,
-                                });
-                            }
-                        }
-                    }
-
-                    return Ok(bytes);
-                } else if attempt <= 3 {
-                    continue;
-                } else if let Err(err) = bytes {
-                    return Err(Error::FetchError {
-                        inner: err,
-                        item: url.to_string(),
-                    });
+    DOWNLOADER
+        .get_or_init(Downloader::default)
+        .download_metadata(url, sha1, size)
+        .await
+}
+
+/// Downloads a metadata file, unless it has not changed since the download the
+/// validator is from
+pub async fn download_file_conditional(
+    url: &str,
+    validator: Option<&download::Validator>,
+) -> Result<download::Conditional<bytes::Bytes>, Error> {
+    DOWNLOADER
+        .get_or_init(Downloader::default)
+        .download_file_conditional(url, validator)
+        .await
+}
+
+/// Downloads a file with retry and checksum functionality, returning the
+/// headers of the response alongside it
+pub async fn download_file_with_headers(
+    url: &str,
+    sha1: Option<&str>,
+) -> Result<(bytes::Bytes, reqwest::header::HeaderMap), Error> {
+    DOWNLOADER
+        .get_or_init(Downloader::default)
+        .download_file_with_headers(url, sha1)
+        .await
+}
+
+/// Downloads a file to the specified path, resuming from the data already
+/// present there
+pub async fn download_file_resumable(
+    url: &str,
+    sha1: Option<&str>,
+    partial_path: &std::path::Path,
+) -> Result<(), Error> {
+    DOWNLOADER
+        .get_or_init(Downloader::default)
+        .download_file_resumable(url, sha1, partial_path)
+        .await
+}
+
+/// Downloads the files to their destinations, with up to the given amount of
+/// downloads at the same time, until the cancellation token is cancelled
+pub async fn download_files(
+    files: Vec<download::FileDownload>,
+    concurrency: usize,
+    cancel: Option<&download::CancellationToken>,
+) -> Result<(), Error> {
+    DOWNLOADER
+        .get_or_init(Downloader::default)
+        .download_files(files, concurrency, cancel)
+        .await
+}
+
+/// Downloads an asset object from the resources base URL of the downloader
+pub async fn download_asset(
+    asset: &minecraft::Asset,
+) -> Result<bytes::Bytes, Error> {
+    DOWNLOADER
+        .get_or_init(Downloader::default)
+        .download_asset(asset)
+        .await
+}
+
+/// Downloads a file of a version, such as a game jar, bounded by its size
+pub async fn download_version_file(
+    download: &minecraft::Download,
+) -> Result<bytes::Bytes, Error> {
+    DOWNLOADER
+        .get_or_init(Downloader::default)
+        .download_version_file(download)
+        .await
+}
+
+/// Downloads the primary artifact of a library, falling back to the library
+/// repositories of the downloader
+pub async fn download_library(
+    library: &minecraft::Library,
+) -> Result<bytes::Bytes, Error> {
+    DOWNLOADER
+        .get_or_init(Downloader::default)
+        .download_library(library)
+        .await
+}
+
+/// Checks whether a file exists and how large it is without downloading it
+pub async fn probe_file(url: &str) -> Result<download::FileProbe, Error> {
+    DOWNLOADER
+        .get_or_init(Downloader::default)
+        .probe_file(url)
+        .await
+}
+
+/// Downloads a file to the destination, only replacing it once the file is
+/// complete and its checksum is validated
+pub async fn download_to_file(
+    url: &str,
+    sha1: Option<&str>,
+    dest: &std::path::Path,
+) -> Result<(), Error> {
+    DOWNLOADER
+        .get_or_init(Downloader::default)
+        .download_to_file(url, sha1, dest)
+        .await
+}
+
+/// Deserializes a document fetched from the URL, attaching the URL and the
+/// model type to the error on failure
+pub(crate) fn parse_json<T: serde::de::DeserializeOwned>(
+    url: &str,
+    bytes: &[u8],
+) -> Result<T, Error> {
+    serde_json::from_slice(bytes).map_err(|err| Error::DeserializeContext {
+        url: url.to_string(),
+        type_name: std::any::type_name::<T>(),
+        source: err,
+    })
+}
+
+/// Deserializes a document, failing with [`Error::UnknownField`] if it contains
+/// fields which would be lost when serializing the model again. Fields which
+/// aren't serialized again only because they are empty aren't reported.
+/// Fields captured in a flattened map of unknown fields need to be checked by
+/// the caller
+pub(crate) fn parse_json_strict<T>(bytes: &[u8]) -> Result<T, Error>
+where
+    T: serde::de::DeserializeOwned + serde::Serialize,
+{
+    let input: serde_json::Value = serde_json::from_slice(bytes)?;
+    let parsed: T = serde_json::from_value(input.clone())?;
+    let output = serde_json::to_value(&parsed)?;
+
+    match find_unknown_field(&input, &output, "") {
+        Some(field) => Err(Error::UnknownField {
+            field,
+            type_name: std::any::type_name::<T>(),
+        }),
+        None => Ok(parsed),
+    }
+}
+
+/// Returns the path of the first field of the input missing in the output
+fn find_unknown_field(
+    input: &serde_json::Value,
+    output: &serde_json::Value,
+    path: &str,
+) -> Option<String> {
+    use serde_json::Value;
+
+    let join = |key: &str| {
+        if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", path, key)
+        }
+    };
+
+    match (input, output) {
+        (Value::Object(input), Value::Object(output)) => {
+            input.iter().find_map(|(key, value)| match output.get(key) {
+                Some(output) => find_unknown_field(value, output, &join(key)),
+                None => {
+                    let empty = match value {
+                        Value::Null => true,
+                        Value::Bool(x) => !x,
+                        Value::String(x) => x.is_empty(),
+                        Value::Array(x) => x.is_empty(),
+                        Value::Object(x) => x.is_empty(),
+                        Value::Number(_) => false,
+                    };
+
+                    Some(join(key)).filter(|_| !empty)
+                }
+            })
+        }
+        (Value::Array(input), Value::Array(output)) => {
+            input.iter().zip(output).enumerate().find_map(
+                |(index, (input, output))| {
+                    find_unknown_field(input, output, &join(&index.to_string()))
+                },
+            )
+        }
+        _ => None,
+    }
+}
+
+/// Serializes a model with its fields in the order of the document it was
+/// deserialized from, so it diffs cleanly against it. The model structs
+/// serialize their own fields in a fixed order, and fields the original
+/// doesn't have are placed after those it has
+#[cfg(feature = "preserve_order")]
+pub fn to_value_ordered_like<T: serde::Serialize>(
+    value: &T,
+    original: &serde_json::Value,
+) -> Result<serde_json::Value, Error> {
+    let mut value = serde_json::to_value(value)?;
+    order_like(&mut value, original);
+
+    Ok(value)
+}
+
+/// Reorders the fields of the objects in the value like those of the original
+#[cfg(feature = "preserve_order")]
+fn order_like(value: &mut serde_json::Value, original: &serde_json::Value) {
+    use serde_json::Value;
+
+    match (value, original) {
+        (Value::Object(value), Value::Object(original)) => {
+            let mut rest = std::mem::take(value);
+
+            for (key, original) in original {
+                if let Some(mut field) = rest.shift_remove(key) {
+                    order_like(&mut field, original);
+                    value.insert(key.clone(), field);
                 }
             }
-            Err(_) if attempt <= 3 => continue,
-            Err(err) => {
-                return Err(Error::FetchError {
-                    inner: err,
-                    item: url.to_string(),
-                })
+            value.extend(rest);
+        }
+        (Value::Array(value), Value::Array(original)) => {
+            for (value, original) in value.iter_mut().zip(original) {
+                order_like(value, original);
             }
         }
+        _ => {}
     }
+}
+
+/// Encodes a model with bincode to be cached, prefixed with the format version
+/// of the models, [`minecraft::CURRENT_FORMAT_VERSION`], which
+/// [`decode_cached`] checks
+#[cfg(feature = "bincode")]
+pub fn encode_cached<T: bincode::Encode>(value: &T) -> Result<Vec<u8>, Error> {
+    let config = bincode::config::standard();
+    let mut bytes = bincode::encode_to_vec(
+        minecraft::CURRENT_FORMAT_VERSION as u64,
+        config,
+    )?;
+    bytes.extend(bincode::encode_to_vec(value, config)?);
+
+    Ok(bytes)
+}
+
+/// Decodes a model cached with [`encode_cached`]. As bincode can't tell if the
+/// models changed since then, a cache of another format version is rejected
+/// with [`Error::UnsupportedFormatVersion`] instead of being decoded
+#[cfg(feature = "bincode")]
+pub fn decode_cached<T: bincode::Decode<()>>(bytes: &[u8]) -> Result<T, Error> {
+    let config = bincode::config::standard();
+    let (found, read): (u64, usize) =
+        bincode::decode_from_slice(bytes, config)?;
+
+    if found != minecraft::CURRENT_FORMAT_VERSION as u64 {
+        return Err(Error::UnsupportedFormatVersion {
+            found: found as usize,
+            supported: minecraft::CURRENT_FORMAT_VERSION,
+        });
+    }
+
+    Ok(bincode::decode_from_slice(&bytes[read..], config)?.0)
+}
+
+/// Checks that a document's format version, if it has one, is not newer than
+/// the supported one
+pub(crate) fn check_format_version(
+    found: Option<usize>,
+    supported: usize,
+) -> Result<(), Error> {
+    match found {
+        Some(found) if found > supported => {
+            Err(Error::UnsupportedFormatVersion { found, supported })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Computes a checksum of the file at the path, reading it in chunks instead
+/// of loading it into memory at once
+pub async fn get_file_hash(path: &std::path::Path) -> Result<String, Error> {
+    let path = path.to_path_buf();
+
+    tokio::task::spawn_blocking(move || {
+        use std::io::Read;
 
-    unreachable!()
+        let mut file = std::fs::File::open(path)?;
+        let mut hasher = sha1::Sha1::new();
+        let mut buffer = vec![0; 64 * 1024];
+
+        loop {
+            let read = file.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+        }
+
+        Ok(hasher.hexdigest())
+    })
+    .await?
 }
 
 /// Computes a checksum of the input bytes
@@ -271,3 +1097,260 @@ pub async fn get_hash(bytes: bytes::Bytes) -> Result<String, Error> {
 
     Ok(hash)
 }
+
+/// Computes the SHA1 hashes of many inputs like [`get_hash`], with at most
+/// `max_blocking` of them hashed on the blocking thread pool at once. The
+/// results are in the order of the inputs
+pub async fn get_hashes(
+    inputs: Vec<bytes::Bytes>,
+    max_blocking: usize,
+) -> Vec<Result<String, Error>> {
+    let semaphore =
+        std::sync::Arc::new(tokio::sync::Semaphore::new(max_blocking.max(1)));
+
+    let handles = inputs
+        .into_iter()
+        .map(|bytes| {
+            let semaphore = semaphore.clone();
+            tokio::spawn(async move {
+                let _permit = semaphore.acquire_owned().await?;
+
+                get_hash(bytes).await
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut hashes = Vec::with_capacity(handles.len());
+    for handle in handles {
+        hashes.push(handle.await.unwrap_or_else(|err| Err(err.into())));
+    }
+
+    hashes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_from_artifact_defaults_to_jar() {
+        assert_eq!(
+            get_path_from_artifact("org.ow2.asm:asm:9.5").unwrap(),
+            "org/ow2/asm/asm/9.5/asm-9.5.jar"
+        );
+        assert_eq!(
+            get_path_from_artifact("org.lwjgl:lwjgl:3.3.1:natives-linux")
+                .unwrap(),
+            "org/lwjgl/lwjgl/3.3.1/lwjgl-3.3.1-natives-linux.jar"
+        );
+    }
+
+    #[test]
+    fn path_from_artifact_with_extension() {
+        assert_eq!(
+            get_path_from_artifact("de.oceanlabs.mcp:mcp_config:1.20.1@zip")
+                .unwrap(),
+            "de/oceanlabs/mcp/mcp_config/1.20.1/mcp_config-1.20.1.zip"
+        );
+        assert_eq!(
+            get_path_from_artifact("net.minecraftforge:forge:1.20.1@pom")
+                .unwrap(),
+            "net/minecraftforge/forge/1.20.1/forge-1.20.1.pom"
+        );
+        assert_eq!(
+            get_path_from_artifact("com.example:natives:1.0@tar.gz").unwrap(),
+            "com/example/natives/1.0/natives-1.0.tar.gz"
+        );
+    }
+
+    #[test]
+    fn path_from_artifact_with_classifier_and_extension() {
+        assert_eq!(
+            get_path_from_artifact(
+                "de.oceanlabs.mcp:mcp_config:1.20.1:mappings@zip"
+            )
+            .unwrap(),
+            "de/oceanlabs/mcp/mcp_config/1.20.1/mcp_config-1.20.1-mappings.zip"
+        );
+        assert_eq!(
+            get_path_from_artifact("com.example:natives:1.0:linux@tar.gz")
+                .unwrap(),
+            "com/example/natives/1.0/natives-1.0-linux.tar.gz"
+        );
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn version_info_bincode_round_trip() {
+        let mut json: serde_json::Value = serde_json::from_slice(
+            &std::fs::read(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/fixtures/1.19.json"
+            ))
+            .unwrap(),
+        )
+        .unwrap();
+        json["_comment_"] = serde_json::json!(["Preserved", { "nested": 1 }]);
+        let info: minecraft::VersionInfo =
+            serde_json::from_value(json).unwrap();
+
+        let decoded: minecraft::VersionInfo =
+            decode_cached(&encode_cached(&info).unwrap()).unwrap();
+
+        assert_eq!(decoded, info);
+        assert!(decoded.extra.contains_key("_comment_"));
+        assert!(decoded.extra.contains_key("logging"));
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn partial_version_bincode_round_trip() {
+        let partial: modded::PartialVersionInfo =
+            serde_json::from_value(serde_json::json!({
+                "id": "fabric-loader-0.14.21-1.20.1",
+                "inheritsFrom": "1.20.1",
+                "releaseTime": "2023-06-12T00:00:00+00:00",
+                "time": "2023-06-12T00:00:00+00:00",
+                "mainClass": "net.fabricmc.loader.impl.launch.knot.KnotClient",
+                "type": "release",
+                "libraries": [],
+                "_comment_": ["Preserved"],
+            }))
+            .unwrap();
+
+        let decoded: modded::PartialVersionInfo =
+            decode_cached(&encode_cached(&partial).unwrap()).unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&decoded).unwrap(),
+            serde_json::to_value(&partial).unwrap()
+        );
+    }
+
+    #[test]
+    fn path_from_artifact_rejects_invalid_extensions() {
+        assert!(get_path_from_artifact("com.example:name:1.0@").is_err());
+        let two_extensions = "com.example:name:1.0@zip@jar";
+        assert!(get_path_from_artifact(two_extensions).is_err());
+        assert!(get_path_from_artifact("com.example:name:1.0:c@").is_err());
+    }
+
+    /// Coordinates whose path could escape the libraries directory
+    const UNSAFE_ARTIFACTS: &[&str] = &[
+        "..:..:..",
+        "com.example:..:1.0",
+        "com.example:name:..",
+        "com.example:name:1.0:..",
+        "com.example:name:1.0@..",
+        "com/example:name:1.0",
+        "com.example:na/me:1.0",
+        "com.example:name:1.0/../..",
+        "com.example:name:1.0:natives/linux",
+        "com.example:name:1.0@/jar",
+        "com\\example:name:1.0",
+        "com.example:na\\me:1.0",
+        "com.example:name:1.0:natives\\linux",
+        "com.example:name\0:1.0",
+        "com.example:name:1.0:natives\0",
+        ":name:1.0",
+        "com..example:name:1.0",
+        ".com.example:name:1.0",
+        "com.example.:name:1.0",
+        "com.example:name:1.0:",
+    ];
+
+    fn is_parse_error<T>(result: Result<T, Error>) -> bool {
+        matches!(result, Err(Error::ParseError(_)))
+    }
+
+    #[test]
+    fn unsafe_artifacts_are_rejected() {
+        for artifact in UNSAFE_ARTIFACTS {
+            assert!(
+                is_parse_error(get_path_from_artifact(artifact)),
+                "{}",
+                artifact
+            );
+            assert!(
+                is_parse_error(artifact.parse::<MavenCoordinate>()),
+                "{}",
+                artifact
+            );
+        }
+        assert!(is_parse_error("..".parse::<MavenCoordinate>()));
+    }
+
+    #[test]
+    fn artifact_paths_stay_in_the_directory() {
+        let libraries_dir = std::path::Path::new("/libraries");
+
+        for artifact in UNSAFE_ARTIFACTS.iter().chain(&[
+            "com.example:name:1.0",
+            "com.example:name:1.0:natives-linux@tar.gz",
+            "com.example:name.with.dots:1.0-rc.1",
+            "com.example:name:1..0",
+            "a:b:c",
+        ]) {
+            if let Ok(path) = get_path_from_artifact(artifact) {
+                let path = std::path::Path::new(&path);
+
+                assert!(
+                    path.components()
+                        .all(|x| matches!(x, std::path::Component::Normal(_))),
+                    "{}",
+                    artifact
+                );
+                assert!(
+                    libraries_dir.join(path).starts_with(libraries_dir),
+                    "{}",
+                    artifact
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn base_coordinate_and_ga() {
+        for (artifact, base) in [
+            ("org.ow2.asm:asm:9.5", "org.ow2.asm:asm:9.5"),
+            (
+                "org.lwjgl:lwjgl:3.3.1:natives-linux",
+                "org.lwjgl:lwjgl:3.3.1",
+            ),
+            (
+                "de.oceanlabs.mcp:mcp_config:1.20.1@zip",
+                "de.oceanlabs.mcp:mcp_config:1.20.1",
+            ),
+            (
+                "de.oceanlabs.mcp:mcp_config:1.20.1:mappings@txt",
+                "de.oceanlabs.mcp:mcp_config:1.20.1",
+            ),
+        ] {
+            assert_eq!(maven_base_coordinate(artifact).unwrap(), base);
+            assert_eq!(
+                maven_ga(artifact).unwrap(),
+                base.rsplit_once(':').unwrap().0
+            );
+        }
+    }
+
+    #[test]
+    fn base_coordinate_and_ga_reject_malformed_artifacts() {
+        for artifact in [
+            "",
+            "org.ow2.asm",
+            "org.ow2.asm:asm",
+            "org.ow2.asm:asm:9.5:natives:extra",
+            "org.ow2.asm:asm:9.5@",
+            "org.ow2.asm::9.5",
+            "..:..:..",
+        ] {
+            assert!(
+                is_parse_error(maven_base_coordinate(artifact)),
+                "{}",
+                artifact
+            );
+            assert!(is_parse_error(maven_ga(artifact)), "{}", artifact);
+        }
+    }
+}
@@ -1,9 +1,17 @@
-use crate::modded::{Processor, SidedDataEntry};
-use crate::{download_file, Error};
-use chrono::{DateTime, Utc};
+use crate::download::{Conditional, Validator};
+use crate::modded::{Processor, Side, SidedDataEntry};
+use crate::{
+    check_format_version, download_file_conditional, download_metadata,
+    download_metadata_with_size, parse_json, parse_json_strict, Error, Map,
+    UnknownFields,
+};
+use chrono::{DateTime, TimeZone, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 
 #[cfg(feature = "bincode")]
 use bincode::{Decode, Encode};
@@ -12,7 +20,7 @@ use bincode::{Decode, Encode};
 pub const CURRENT_FORMAT_VERSION: usize = 0;
 
 #[cfg_attr(feature = "bincode", derive(Encode, Decode))]
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq, Hash)]
 #[serde(rename_all = "snake_case")]
 /// The version type
 pub enum VersionType {
@@ -36,12 +44,26 @@ impl VersionType {
             VersionType::OldBeta => "old_beta",
         }
     }
+
+    /// Whether the version type is offered to players by default, which is only
+    /// the case for releases
+    pub fn is_stable(&self) -> bool {
+        matches!(self, VersionType::Release)
+    }
+
+    /// Whether the version type is one of the versions from before the game was
+    /// released
+    pub fn is_historical(&self) -> bool {
+        matches!(self, VersionType::OldAlpha | VersionType::OldBeta)
+    }
 }
 
 #[cfg_attr(feature = "bincode", derive(Encode, Decode))]
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
-/// A game version of Minecraft
+/// A game version of Minecraft.
+/// Versions are only equal if all of their fields are, including their times,
+/// but only the `id` is hashed
 pub struct Version {
     /// A unique identifier of the version
     pub id: String,
@@ -56,9 +78,14 @@ pub struct Version {
     /// The time this version was released
     #[cfg_attr(feature = "bincode", bincode(with_serde))]
     pub release_time: DateTime<Utc>,
-    /// The SHA1 hash of the additional information about the version
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    /// (v2 manifest only) The SHA1 hash of the additional information about the version.
+    /// This is empty when parsed from the v1 manifest
     pub sha1: String,
-    /// Whether the version supports the latest player safety features
+    #[serde(default)]
+    /// (v2 manifest only) Whether the version supports the latest player safety features.
+    /// `0` means the version predates them, `1` means it supports them.
+    /// This is `0` when parsed from the v1 manifest
     pub compliance_level: u32,
     #[serde(skip_serializing_if = "Option::is_none")]
     /// (GDLauncher Provided) The link to the assets index for this version
@@ -73,8 +100,57 @@ pub struct Version {
     pub java_profile: Option<MinecraftJavaProfile>,
 }
 
+impl Version {
+    /// Returns the download of the GDLauncher provided assets index of the
+    /// version, if present. Its size is unknown and left as zero.
+    /// The canonical source of the assets index is Mojang's
+    /// [`VersionInfo::asset_index`], this is only preferred when using the
+    /// GDLauncher mirror
+    pub fn assets_index_download(&self) -> Option<Download> {
+        Some(Download {
+            sha1: self.assets_index_sha1.clone().unwrap_or_default(),
+            size: 0,
+            url: self.assets_index_url.clone()?,
+        })
+    }
+
+    /// Fetches the assets index of the version from the GDLauncher provided
+    /// download, without fetching the version info first. Fails if the
+    /// version has no such download, as it is absent from other manifests
+    pub async fn fetch_assets_index(&self) -> Result<AssetsIndex, Error> {
+        let download = self.assets_index_download().ok_or_else(|| {
+            Error::ParseError(format!(
+                "Version {} has no assets index URL, which only the GDLauncher mirror provides",
+                self.id
+            ))
+        })?;
+        let sha1 = Some(&*download.sha1).filter(|x| !x.is_empty());
+
+        parse_json(
+            &download.url,
+            &download_metadata(&download.url, sha1).await?,
+        )
+    }
+
+    /// Whether the bytes of a version info, such as a cached one, match the
+    /// hash of the version in the manifest. Versions from the v1 manifest
+    /// have no hash, so nothing matches them
+    pub fn verify_info_bytes(&self, bytes: &[u8]) -> bool {
+        !self.sha1.is_empty()
+            && sha1::Sha1::from(bytes)
+                .hexdigest()
+                .eq_ignore_ascii_case(&self.sha1)
+    }
+}
+
+impl Hash for Version {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
 #[cfg_attr(feature = "bincode", derive(Encode, Decode))]
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq, Hash)]
 #[serde(rename_all = "kebab-case")]
 /// Java profile required to run this mc version
 pub enum MinecraftJavaProfile {
@@ -86,6 +162,8 @@ pub enum MinecraftJavaProfile {
     JavaRuntimeBeta,
     /// Java 17
     JavaRuntimeGamma,
+    /// Java 21
+    JavaRuntimeDelta,
     /// Java 14
     MinecraftJavaExe,
 }
@@ -98,6 +176,7 @@ impl MinecraftJavaProfile {
             MinecraftJavaProfile::JavaRuntimeAlpha => "java-runtime-alpha",
             MinecraftJavaProfile::JavaRuntimeBeta => "java-runtime-beta",
             MinecraftJavaProfile::JavaRuntimeGamma => "java-runtime-gamma",
+            MinecraftJavaProfile::JavaRuntimeDelta => "java-runtime-delta",
             MinecraftJavaProfile::MinecraftJavaExe => "minecraft-java-exe",
         }
     }
@@ -112,6 +191,7 @@ impl TryFrom<&str> for MinecraftJavaProfile {
             "java-runtime-alpha" => Ok(MinecraftJavaProfile::JavaRuntimeAlpha),
             "java-runtime-beta" => Ok(MinecraftJavaProfile::JavaRuntimeBeta),
             "java-runtime-gamma" => Ok(MinecraftJavaProfile::JavaRuntimeGamma),
+            "java-runtime-delta" => Ok(MinecraftJavaProfile::JavaRuntimeDelta),
             "minecraft-java-exe" => Ok(MinecraftJavaProfile::MinecraftJavaExe),
             _ => Err(Error::InvalidMinecraftJavaProfile(value.to_string())),
         }
@@ -132,27 +212,174 @@ pub struct LatestVersion {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 /// Data of all game versions of Minecraft
 pub struct VersionManifest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// The format version of the manifest. Only present in manifests generated
+    /// with this crate, see [`CURRENT_FORMAT_VERSION`]
+    pub format_version: Option<usize>,
     /// A struct containing the latest snapshot and release of the game
     pub latest: LatestVersion,
     /// A list of game versions of Minecraft
     pub versions: Vec<Version>,
 }
 
+impl VersionManifest {
+    /// Deserializes a manifest, failing with [`Error::UnknownField`] if it has
+    /// a field which isn't modelled, to notice when the format changes
+    pub fn from_slice_strict(bytes: &[u8]) -> Result<VersionManifest, Error> {
+        parse_json_strict(bytes)
+    }
+
+    /// Deserializes a manifest from a reader, such as a buffered file, without
+    /// reading it into memory first
+    pub fn from_reader<R: std::io::Read>(
+        reader: R,
+    ) -> Result<VersionManifest, Error> {
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    /// Reads an uncompressed manifest from a file
+    pub fn from_path(path: &Path) -> Result<VersionManifest, Error> {
+        Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+    }
+
+    /// Parses a gzip-compressed manifest
+    #[cfg(feature = "gzip")]
+    pub fn from_gzip(bytes: &[u8]) -> Result<VersionManifest, Error> {
+        Ok(serde_json::from_reader(flate2::read::GzDecoder::new(
+            bytes,
+        ))?)
+    }
+
+    /// Reads a gzip-compressed manifest from a file
+    #[cfg(feature = "gzip")]
+    pub fn from_gzip_path(path: &Path) -> Result<VersionManifest, Error> {
+        VersionManifest::from_gzip(&std::fs::read(path)?)
+    }
+
+    /// Serializes and gzip-compresses the manifest
+    #[cfg(feature = "gzip")]
+    pub fn to_gzip(&self) -> Result<Vec<u8>, Error> {
+        use std::io::Write;
+
+        let mut encoder = flate2::write::GzEncoder::new(
+            Vec::new(),
+            flate2::Compression::default(),
+        );
+        encoder.write_all(&serde_json::to_vec(self)?)?;
+
+        Ok(encoder.finish()?)
+    }
+
+    /// Returns the stable versions of the manifest, see
+    /// [`VersionType::is_stable`]
+    pub fn releases_only(&self) -> impl Iterator<Item = &Version> {
+        self.versions.iter().filter(|x| x.type_.is_stable())
+    }
+
+    /// Returns the versions of the manifest sorted by their release time,
+    /// oldest first unless descending. Versions released at the same time are
+    /// ordered by their ID in the same direction
+    pub fn sorted_by_date(&self, descending: bool) -> Vec<&Version> {
+        let mut versions = self.versions.iter().collect::<Vec<_>>();
+        versions.sort_by(|a, b| {
+            let ordering = a
+                .release_time
+                .cmp(&b.release_time)
+                .then_with(|| a.id.cmp(&b.id));
+
+            if descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+
+        versions
+    }
+
+    /// Keeps only the `n` most recently released versions of the types, or of
+    /// any type if none are given, in their order in the manifest. The latest
+    /// versions are recomputed from the retained ones: the latest release is
+    /// the newest release and the latest snapshot the newest release or
+    /// snapshot, as in the manifests of Mojang. They are left empty if no
+    /// such version is retained
+    pub fn truncate_recent(&mut self, n: usize, types: &[VersionType]) {
+        let retained = self
+            .sorted_by_date(true)
+            .into_iter()
+            .filter(|x| types.is_empty() || types.contains(&x.type_))
+            .take(n)
+            .map(|x| x.id.clone())
+            .collect::<HashSet<_>>();
+        self.versions.retain(|x| retained.contains(&x.id));
+
+        let newest = |types: &[VersionType]| {
+            self.sorted_by_date(true)
+                .into_iter()
+                .find(|x| types.contains(&x.type_))
+                .map(|x| x.id.clone())
+                .unwrap_or_default()
+        };
+        self.latest = LatestVersion {
+            release: newest(&[VersionType::Release]),
+            snapshot: newest(&[VersionType::Release, VersionType::Snapshot]),
+        };
+    }
+}
+
 /// The URL to the version manifest
 pub const VERSION_MANIFEST_URL: &str =
     "https://piston-meta.mojang.com/mc/game/version_manifest_v2.json";
+/// The URL to the legacy v1 version manifest, which lacks the `sha1` and
+/// `compliance_level` fields of its versions
+pub const VERSION_MANIFEST_V1_URL: &str =
+    "https://piston-meta.mojang.com/mc/game/version_manifest.json";
 
 /// Fetches a version manifest from the specified URL. If no URL is specified, the default is used.
+/// Fails if the manifest has a newer format version than [`CURRENT_FORMAT_VERSION`]
 pub async fn fetch_version_manifest(
     url: Option<&str>,
 ) -> Result<VersionManifest, Error> {
-    Ok(serde_json::from_slice(
-        &download_file(url.unwrap_or(VERSION_MANIFEST_URL), None).await?,
-    )?)
+    Ok(fetch_version_manifest_raw(url).await?.0)
+}
+
+/// Fetches a version manifest like [`fetch_version_manifest`], returning the
+/// bytes it was parsed from alongside it
+pub async fn fetch_version_manifest_raw(
+    url: Option<&str>,
+) -> Result<(VersionManifest, bytes::Bytes), Error> {
+    let url = url.unwrap_or(VERSION_MANIFEST_URL);
+    let bytes = download_metadata(url, None).await?;
+    let manifest: VersionManifest = parse_json(url, &bytes)?;
+
+    check_format_version(manifest.format_version, CURRENT_FORMAT_VERSION)?;
+
+    Ok((manifest, bytes))
+}
+
+/// Fetches a version manifest like [`fetch_version_manifest`], unless it has
+/// not changed since the fetch the validator is from
+pub async fn fetch_version_manifest_conditional(
+    url: Option<&str>,
+    validator: Option<&Validator>,
+) -> Result<Conditional<VersionManifest>, Error> {
+    let url = url.unwrap_or(VERSION_MANIFEST_URL);
+
+    download_file_conditional(url, validator)
+        .await?
+        .try_map(|bytes| {
+            let manifest: VersionManifest = parse_json(url, &bytes)?;
+            check_format_version(
+                manifest.format_version,
+                CURRENT_FORMAT_VERSION,
+            )?;
+
+            Ok(manifest)
+        })
 }
 
 #[cfg_attr(feature = "bincode", derive(Encode, Decode))]
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 /// Information about the assets of the game
 pub struct AssetIndex {
@@ -161,16 +388,16 @@ pub struct AssetIndex {
     /// The SHA1 hash of the assets index
     pub sha1: String,
     /// The size of the assets index
-    pub size: u32,
+    pub size: u64,
     /// The size of the game version's assets
-    pub total_size: u32,
+    pub total_size: u64,
     /// A URL to a file which contains information about the version's assets
     pub url: String,
 }
 
 #[cfg_attr(feature = "bincode", derive(Encode, Decode))]
 #[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Hash, Clone)]
-#[serde(rename_all = "snake_case")]
+#[serde(from = "String", into = "String")]
 /// The type of download
 pub enum DownloadType {
     /// The download is for the game client
@@ -183,22 +410,61 @@ pub enum DownloadType {
     ServerMappings,
     /// The download is for the windows server
     WindowsServer,
+    /// A type of download which isn't known, kept so that versions with new
+    /// types of downloads still parse
+    Other(String),
+}
+
+impl DownloadType {
+    /// Converts the download type to a string
+    pub fn as_str(&self) -> &str {
+        match self {
+            DownloadType::Client => "client",
+            DownloadType::ClientMappings => "client_mappings",
+            DownloadType::Server => "server",
+            DownloadType::ServerMappings => "server_mappings",
+            DownloadType::WindowsServer => "windows_server",
+            DownloadType::Other(name) => name,
+        }
+    }
+}
+
+impl From<String> for DownloadType {
+    fn from(name: String) -> Self {
+        match &*name {
+            "client" => DownloadType::Client,
+            "client_mappings" => DownloadType::ClientMappings,
+            "server" => DownloadType::Server,
+            "server_mappings" => DownloadType::ServerMappings,
+            "windows_server" => DownloadType::WindowsServer,
+            _ => DownloadType::Other(name),
+        }
+    }
+}
+
+impl From<DownloadType> for String {
+    fn from(type_: DownloadType) -> Self {
+        match type_ {
+            DownloadType::Other(name) => name,
+            type_ => type_.as_str().to_string(),
+        }
+    }
 }
 
 #[cfg_attr(feature = "bincode", derive(Encode, Decode))]
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 /// Download information of a file
 pub struct Download {
     /// The SHA1 hash of the file
     pub sha1: String,
     /// The size of the file
-    pub size: u32,
+    pub size: u64,
     /// The URL where the file can be downloaded
     pub url: String,
 }
 
 #[cfg_attr(feature = "bincode", derive(Encode, Decode))]
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 /// Download information of a library
 pub struct LibraryDownload {
     /// The path that the library should be saved to
@@ -206,13 +472,13 @@ pub struct LibraryDownload {
     /// The SHA1 hash of the library
     pub sha1: String,
     /// The size of the library
-    pub size: u32,
+    pub size: u64,
     /// The URL where the library can be downloaded
     pub url: String,
 }
 
 #[cfg_attr(feature = "bincode", derive(Encode, Decode))]
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 /// A list of files that should be downloaded for libraries
 pub struct LibraryDownloads {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -221,7 +487,7 @@ pub struct LibraryDownloads {
     #[serde(skip_serializing_if = "Option::is_none")]
     /// Conditional files that may be needed to be downloaded alongside the library
     /// The HashMap key specifies a classifier as additional information for downloading files
-    pub classifiers: Option<HashMap<String, LibraryDownload>>,
+    pub classifiers: Option<Map<String, LibraryDownload>>,
 }
 
 #[cfg_attr(feature = "bincode", derive(Encode, Decode))]
@@ -258,8 +524,67 @@ pub enum Os {
     Unknown,
 }
 
+impl Os {
+    /// Returns the OS this program is running on
+    pub fn current() -> Os {
+        match (std::env::consts::OS, std::env::consts::ARCH) {
+            ("macos", "aarch64") => Os::OsxArm64,
+            ("macos", _) => Os::Osx,
+            ("windows", "aarch64") => Os::WindowsArm64,
+            ("windows", _) => Os::Windows,
+            ("linux", "aarch64") => Os::LinuxArm64,
+            ("linux", "arm") => Os::LinuxArm32,
+            ("linux", _) => Os::Linux,
+            _ => Os::Unknown,
+        }
+    }
+
+    /// Returns the OS without its architecture, as named by Mojang's rules
+    pub fn base(&self) -> Os {
+        match self {
+            Os::Osx | Os::OsxArm64 => Os::Osx,
+            Os::Windows | Os::WindowsArm64 => Os::Windows,
+            Os::Linux | Os::LinuxArm64 | Os::LinuxArm32 => Os::Linux,
+            Os::Unknown => Os::Unknown,
+        }
+    }
+
+    /// Returns the architecture of the OS, named like
+    /// [`std::env::consts::ARCH`]. The OS this program is running on, see
+    /// [`Os::current`], has the architecture of this program, which tells
+    /// 32-bit `x86` hosts apart. Of the other variants, the ones without an
+    /// architecture are `x86_64`, and [`Os::Unknown`] has the architecture of
+    /// this program
+    pub fn arch(&self) -> &'static str {
+        if *self == Os::current() {
+            return std::env::consts::ARCH;
+        }
+
+        match self {
+            Os::Osx | Os::Windows | Os::Linux => "x86_64",
+            Os::OsxArm64 | Os::WindowsArm64 | Os::LinuxArm64 => "aarch64",
+            Os::LinuxArm32 => "arm",
+            Os::Unknown => std::env::consts::ARCH,
+        }
+    }
+
+    /// Returns the pointer width of the architecture of the OS, `32` or `64`,
+    /// which replaces the `${arch}` placeholder of native classifiers
+    pub fn pointer_width(&self) -> &'static str {
+        pointer_width(self.arch())
+    }
+}
+
+/// Returns the pointer width of the architecture, `32` or `64`
+fn pointer_width(arch: &str) -> &'static str {
+    match arch {
+        "x86" | "arm" => "32",
+        _ => "64",
+    }
+}
+
 #[cfg_attr(feature = "bincode", derive(Encode, Decode))]
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 /// A rule which depends on what OS the user is on
 pub struct OsRule {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -273,8 +598,95 @@ pub struct OsRule {
     pub arch: Option<String>,
 }
 
+impl OsRule {
+    /// Whether the rule matches the OS. A rule naming an OS without an
+    /// architecture also matches its ARM variants. The architecture is
+    /// matched against the one of the OS, see [`Os::arch`], independent of
+    /// the named OS, so a rule with only an architecture matches every OS of
+    /// that architecture. The version is only known for the OS this program
+    /// is running on, see [`os_version`], and rules restricting it never
+    /// match other OSes or if it can't be determined
+    pub fn applies(&self, os: &Os) -> bool {
+        self.applies_on(os, os.arch())
+    }
+
+    /// Whether the rule matches the OS with the architecture
+    fn applies_on(&self, os: &Os, arch: &str) -> bool {
+        if let Some(name) = &self.name {
+            if name != os && name != &os.base() {
+                return false;
+            }
+        }
+
+        if let Some(rule_arch) = &self.arch {
+            if rule_arch != arch {
+                return false;
+            }
+        }
+
+        if let Some(version) = &self.version {
+            let version_of_os = if *os == Os::current() {
+                os_version()
+            } else {
+                None
+            };
+            let matches = regex::Regex::new(version)
+                .ok()
+                .zip(version_of_os)
+                .is_some_and(|(regex, version)| regex.is_match(&version));
+
+            if !matches {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// Returns the version of the OS this program is running on, in the format
+/// Java reports it as and Mojang's rules expect: the kernel release on Linux,
+/// such as `6.1.0-13-amd64`, the product version on macOS, such as `13.4.1`,
+/// and the version with the build number on Windows, such as `10.0.19045`.
+/// Returns `None` if it can't be determined. The version is queried once and
+/// then cached
+pub fn os_version() -> Option<String> {
+    static OS_VERSION: once_cell::sync::Lazy<Option<String>> =
+        once_cell::sync::Lazy::new(query_os_version);
+
+    OS_VERSION.clone()
+}
+
+fn query_os_version() -> Option<String> {
+    fn command_output(program: &str, args: &[&str]) -> Option<String> {
+        let output = std::process::Command::new(program)
+            .args(args)
+            .output()
+            .ok()
+            .filter(|x| x.status.success())?;
+
+        Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    let version = match std::env::consts::OS {
+        "linux" => std::fs::read_to_string("/proc/sys/kernel/osrelease")
+            .ok()
+            .map(|x| x.trim().to_string()),
+        "macos" => command_output("sw_vers", &["-productVersion"]),
+        // `ver` prints `Microsoft Windows [Version 10.0.19045.3448]`
+        "windows" => command_output("cmd", &["/C", "ver"]).and_then(|x| {
+            let version = x.rsplit(' ').next()?.trim_end_matches(']');
+
+            Some(version.splitn(4, '.').take(3).collect::<Vec<_>>().join("."))
+        }),
+        _ => None,
+    };
+
+    version.filter(|x| !x.is_empty())
+}
+
 #[cfg_attr(feature = "bincode", derive(Encode, Decode))]
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 /// A rule which depends on the toggled features of the launcher
 pub struct FeatureRule {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -283,10 +695,42 @@ pub struct FeatureRule {
     #[serde(skip_serializing_if = "Option::is_none")]
     /// Whether the user is using the demo resolution
     pub has_demo_resolution: Option<bool>,
+    #[serde(flatten)]
+    #[cfg_attr(feature = "bincode", bincode(with_serde))]
+    /// The required states of the features which aren't modelled, such as
+    /// `has_quick_plays_support`, by their name
+    pub extra: Map<String, bool>,
+}
+
+impl FeatureRule {
+    /// Whether every feature the rule specifies has the required state.
+    /// Features which aren't modelled are looked up in
+    /// [`FeatureSet::extra`], counting as disabled if they are missing
+    pub fn applies(&self, features: &FeatureSet) -> bool {
+        self.is_demo_user.is_none_or(|x| x == features.is_demo_user)
+            && self
+                .has_demo_resolution
+                .is_none_or(|x| x == features.has_demo_resolution)
+            && self.extra.iter().all(|(name, value)| {
+                features.extra.get(name).copied().unwrap_or(false) == *value
+            })
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// The features toggled in the launcher, which feature rules are checked against
+pub struct FeatureSet {
+    /// Whether the user is in demo mode
+    pub is_demo_user: bool,
+    /// Whether the user is using the demo resolution
+    pub has_demo_resolution: bool,
+    /// The states of the features which aren't modelled by their name, such
+    /// as `has_quick_plays_support`
+    pub extra: HashMap<String, bool>,
 }
 
 #[cfg_attr(feature = "bincode", derive(Encode, Decode))]
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 /// A rule deciding whether a file is downloaded, an argument is used, etc.
 pub struct Rule {
     /// The action the rule takes
@@ -299,8 +743,31 @@ pub struct Rule {
     pub features: Option<FeatureRule>,
 }
 
+impl Rule {
+    /// Whether the conditions of the rule match, regardless of its action
+    pub fn applies(&self, os: &Os, features: &FeatureSet) -> bool {
+        self.os.as_ref().is_none_or(|x| x.applies(os))
+            && self.features.as_ref().is_none_or(|x| x.applies(features))
+    }
+}
+
+/// Evaluates a list of rules the way the vanilla launcher does: if there are
+/// no rules, the action is allowed. Otherwise it is disallowed unless a
+/// matching rule allows it, with the last matching rule taking precedence
+pub fn rules_apply(rules: &[Rule], os: &Os, features: &FeatureSet) -> bool {
+    if rules.is_empty() {
+        return true;
+    }
+
+    rules
+        .iter()
+        .rev()
+        .find(|x| x.applies(os, features))
+        .is_some_and(|x| x.action == RuleAction::Allow)
+}
+
 #[cfg_attr(feature = "bincode", derive(Encode, Decode))]
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 /// Information delegating the extraction of the library
 pub struct LibraryExtract {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -308,8 +775,186 @@ pub struct LibraryExtract {
     pub exclude: Option<Vec<String>>,
 }
 
+impl LibraryExtract {
+    /// Whether the entry of the library's archive is excluded from the
+    /// extraction. Exclusions are prefixes of the entries' paths, such as
+    /// `META-INF/`
+    pub fn is_excluded(&self, entry: &str) -> bool {
+        self.exclude
+            .iter()
+            .flatten()
+            .any(|x| entry.starts_with(&**x))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A native library archive to extract into the natives directory, which is
+/// passed to the game as `-Djava.library.path`
+pub struct NativeExtraction {
+    /// The maven name of the library the natives belong to
+    pub name: String,
+    /// The classifier of the natives archive
+    pub classifier: String,
+    /// The download of the natives archive
+    pub download: LibraryDownload,
+    /// The rules of the extraction of the archive
+    pub extract: LibraryExtract,
+    /// The directory to extract the archive into
+    pub destination: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A file to extract from a natives archive, see [`NativesPlan`]
+pub struct NativeEntry {
+    /// The index of the archive the file is extracted from
+    pub archive: usize,
+    /// The path of the file in the archive
+    pub entry: String,
+    /// The path the file is extracted to
+    pub destination: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A file which is contained in multiple natives archives
+pub struct NativeCollision {
+    /// The path the file is extracted to
+    pub destination: PathBuf,
+    /// The indexes of the archives containing the file, in order. The file of
+    /// the last one is the one extracted
+    pub archives: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// The ordered list of files to extract from natives archives, along with the
+/// files contained in more than one of them
+pub struct NativesPlan {
+    /// The files to extract, in the order of their archives
+    pub entries: Vec<NativeEntry>,
+    /// The files contained in more than one archive
+    pub collisions: Vec<NativeCollision>,
+}
+
+impl NativesPlan {
+    /// Plans the extraction of the natives archives, given the paths of the
+    /// entries of each archive. Directories, entries excluded by the rules of
+    /// their archive and entries which would be extracted outside of the
+    /// destination are skipped. As in the vanilla launcher, if multiple
+    /// archives contain the same file, the one of the last archive wins
+    pub fn new(natives: &[NativeExtraction], entries: &[Vec<String>]) -> Self {
+        let mut planned: Vec<Option<NativeEntry>> = Vec::new();
+        let mut destinations: HashMap<PathBuf, (usize, Vec<usize>)> =
+            HashMap::new();
+
+        for (archive, (native, entries)) in
+            natives.iter().zip(entries).enumerate()
+        {
+            for entry in entries {
+                let relative = Path::new(entry);
+                let enclosed = relative
+                    .components()
+                    .all(|x| matches!(x, std::path::Component::Normal(_)));
+
+                if entry.ends_with('/')
+                    || !enclosed
+                    || native.extract.is_excluded(entry)
+                {
+                    continue;
+                }
+
+                let destination = native.destination.join(relative);
+                let index = planned.len();
+                planned.push(Some(NativeEntry {
+                    archive,
+                    entry: entry.clone(),
+                    destination: destination.clone(),
+                }));
+
+                match destinations.get_mut(&destination) {
+                    Some((previous, archives)) => {
+                        planned[*previous] = None;
+                        *previous = index;
+                        if archives.last() != Some(&archive) {
+                            archives.push(archive);
+                        }
+                    }
+                    None => {
+                        destinations
+                            .insert(destination, (index, vec![archive]));
+                    }
+                }
+            }
+        }
+
+        let mut collisions = destinations
+            .into_iter()
+            .filter(|(_, (_, archives))| archives.len() > 1)
+            .map(|(destination, (_, archives))| NativeCollision {
+                destination,
+                archives,
+            })
+            .collect::<Vec<_>>();
+        collisions.sort_by(|a, b| a.destination.cmp(&b.destination));
+
+        NativesPlan {
+            entries: planned.into_iter().flatten().collect(),
+            collisions,
+        }
+    }
+}
+
+#[cfg(feature = "natives")]
+/// Extracts the natives archives, stored in the libraries directory, into
+/// their destinations according to their [`NativesPlan`], which is returned
+pub async fn extract_natives(
+    natives: &[NativeExtraction],
+    libraries_dir: &Path,
+) -> Result<NativesPlan, Error> {
+    let natives = natives.to_vec();
+    let libraries_dir = libraries_dir.to_path_buf();
+
+    tokio::task::spawn_blocking(move || {
+        let mut archives = natives
+            .iter()
+            .map(|native| {
+                let path = libraries_dir.join(native_archive_path(
+                    &native.name,
+                    &native.classifier,
+                    &native.download,
+                )?);
+
+                Ok(zip::ZipArchive::new(std::fs::File::open(path)?)?)
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let entries = archives
+            .iter_mut()
+            .map(|archive| {
+                (0..archive.len())
+                    .map(|i| Ok(archive.by_index_raw(i)?.name().to_string()))
+                    .collect::<Result<Vec<_>, Error>>()
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let plan = NativesPlan::new(&natives, &entries);
+        for entry in &plan.entries {
+            if let Some(parent) = entry.destination.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            let mut file = archives[entry.archive].by_name(&entry.entry)?;
+            std::io::copy(
+                &mut file,
+                &mut std::fs::File::create(&entry.destination)?,
+            )?;
+        }
+
+        Ok(plan)
+    })
+    .await?
+}
+
 #[cfg_attr(feature = "bincode", derive(Encode, Decode))]
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 /// Information about the java version the game needs
 pub struct JavaVersion {
@@ -320,7 +965,7 @@ pub struct JavaVersion {
 }
 
 #[cfg_attr(feature = "bincode", derive(Encode, Decode))]
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 /// A library which the game relies on to run
 pub struct Library {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -336,107 +981,470 @@ pub struct Library {
     pub url: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     /// Native files that the library relies on
-    pub natives: Option<HashMap<Os, String>>,
+    pub natives: Option<Map<Os, String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     /// Rules deciding whether the library should be downloaded or not
     pub rules: Option<Vec<Rule>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     /// SHA1 Checksums for validating the library's integrity. Only present for forge libraries
     pub checksums: Option<Vec<String>>,
-    #[serde(default = "default_include_in_classpath")]
+    #[serde(
+        default = "default_include_in_classpath",
+        skip_serializing_if = "is_default_include_in_classpath"
+    )]
     /// Whether the library should be included in the classpath at the game's launch
     pub include_in_classpath: bool,
 }
 
-#[derive(Deserialize, Debug, Clone)]
-/// A partial library which should be merged with a full library
-pub struct PartialLibrary {
-    /// The files the library has
-    pub downloads: Option<LibraryDownloads>,
-    /// Rules of the extraction of the file
-    pub extract: Option<LibraryExtract>,
-    /// The maven name of the library. The format is `groupId:artifactId:version`
-    pub name: Option<String>,
-    /// The URL to the repository where the library can be downloaded
-    pub url: Option<String>,
-    /// Native files that the library relies on
-    pub natives: Option<HashMap<Os, String>>,
-    /// Rules deciding whether the library should be downloaded or not
-    pub rules: Option<Vec<Rule>>,
-    /// SHA1 Checksums for validating the library's integrity. Only present for forge libraries
-    pub checksums: Option<Vec<String>>,
-    /// Whether the library should be included in the classpath at the game's launch
-    pub include_in_classpath: Option<bool>,
-}
+impl Library {
+    /// Whether the library is needed on the OS with the given features
+    pub fn applies(&self, os: &Os, features: &FeatureSet) -> bool {
+        rules_apply(self.rules.as_deref().unwrap_or_default(), os, features)
+    }
 
-/// Merges a partial library to make a complete library
-pub fn merge_partial_library(
-    partial: PartialLibrary,
-    mut merge: Library,
-) -> Library {
-    if let Some(downloads) = partial.downloads {
-        if let Some(merge_downloads) = &mut merge.downloads {
-            if let Some(artifact) = downloads.artifact {
-                merge_downloads.artifact = Some(artifact);
-            }
-            if let Some(classifiers) = downloads.classifiers {
-                if let Some(merge_classifiers) =
-                    &mut merge_downloads.classifiers
-                {
-                    for classifier in classifiers {
-                        merge_classifiers.insert(classifier.0, classifier.1);
-                    }
-                } else {
-                    merge_downloads.classifiers = Some(classifiers);
-                }
-            }
-        } else {
-            merge.downloads = Some(downloads)
-        }
+    /// Returns the classifier of the natives of the library for the OS, if it
+    /// has natives for it. The `${arch}` placeholder is replaced with the
+    /// pointer width of the OS, see [`Os::pointer_width`]
+    pub fn native_classifier(&self, os: &Os) -> Option<String> {
+        let natives = self.natives.as_ref()?;
+        let classifier = natives.get(os).or_else(|| natives.get(&os.base()))?;
+
+        Some(classifier.replace("${arch}", os.pointer_width()))
     }
-    if let Some(extract) = partial.extract {
-        merge.extract = Some(extract)
+
+    /// Returns the download of the natives of the library for the OS, if it
+    /// has natives for it
+    pub fn native_download(&self, os: &Os) -> Option<&LibraryDownload> {
+        let classifier = self.native_classifier(os)?;
+
+        self.downloads
+            .as_ref()?
+            .classifiers
+            .as_ref()?
+            .get(&classifier)
     }
-    if let Some(name) = partial.name {
-        merge.name = name
+
+    /// Returns the download of the natives of the library for the OS like
+    /// [`Library::native_download`], failing with
+    /// [`Error::MissingNativeDownload`] if the library has downloads but none
+    /// for the classifier of its natives. Libraries without downloads are
+    /// resolved from their maven repository instead, which isn't an error
+    pub fn try_native_download(
+        &self,
+        os: &Os,
+    ) -> Result<Option<&LibraryDownload>, Error> {
+        let classifier = match self.native_classifier(os) {
+            Some(classifier) => classifier,
+            None => return Ok(None),
+        };
+        let downloads = match &self.downloads {
+            Some(downloads) => downloads,
+            None => return Ok(None),
+        };
+
+        match downloads
+            .classifiers
+            .as_ref()
+            .and_then(|x| x.get(&classifier))
+        {
+            Some(download) => Ok(Some(download)),
+            None => Err(Error::MissingNativeDownload {
+                library: self.name.clone(),
+                classifier,
+            }),
+        }
     }
-    if let Some(url) = partial.url {
-        merge.url = Some(url)
+
+    /// Whether the library looks like it isn't downloaded, but generated by
+    /// the processors of a Forge install profile: its artifact has an empty
+    /// URL and it has no repository to build one from. Libraries contained
+    /// in the installer look the same, which
+    /// [`InstallProfile::library_source`](crate::modded::InstallProfile::library_source)
+    /// tells apart
+    pub fn is_processor_output(&self) -> bool {
+        self.url.is_none()
+            && self
+                .downloads
+                .as_ref()
+                .and_then(|x| x.artifact.as_ref())
+                .is_some_and(|x| x.url.is_empty())
     }
-    if let Some(natives) = partial.natives {
-        if let Some(merge_natives) = &mut merge.natives {
-            for native in natives {
-                merge_natives.insert(native.0, native.1);
-            }
-        } else {
-            merge.natives = Some(natives);
+
+    /// Returns the download of the primary artifact of the library. If the
+    /// library has no explicit artifact download, one is built from its maven
+    /// name and its repository URL, or `default_repo` if it has none. The hash
+    /// is left empty and the size zero when they are unknown
+    pub fn resolve_download(
+        &self,
+        default_repo: &str,
+    ) -> Option<LibraryDownload> {
+        if let Some(artifact) =
+            self.downloads.as_ref().and_then(|x| x.artifact.as_ref())
+        {
+            return Some(artifact.clone());
         }
+
+        let path = crate::get_path_from_artifact(&self.name).ok()?;
+        let url =
+            repository_url(self.url.as_deref().unwrap_or(default_repo), &path);
+
+        Some(LibraryDownload {
+            path,
+            sha1: self
+                .checksums
+                .as_ref()
+                .and_then(|x| x.first().cloned())
+                .unwrap_or_default(),
+            size: 0,
+            url,
+        })
     }
-    if let Some(rules) = partial.rules {
-        if let Some(merge_rules) = &mut merge.rules {
-            for rule in rules {
-                merge_rules.push(rule);
+
+    /// Returns the URLs the library can be downloaded from: the URL of its
+    /// artifact first, then its path in its repository and in each of the
+    /// extra repositories, without duplicates. These can be passed as the
+    /// mirrors of [`crate::download_file_mirrors`] with an empty base
+    pub fn download_candidates(&self, extra_repos: &[&str]) -> Vec<String> {
+        let mut candidates = Vec::new();
+
+        if let Some(artifact) =
+            self.downloads.as_ref().and_then(|x| x.artifact.as_ref())
+        {
+            if !artifact.url.is_empty() {
+                candidates.push(artifact.url.clone());
             }
-        } else {
-            merge.rules = Some(rules)
         }
-    }
-    if let Some(checksums) = partial.checksums {
-        merge.checksums = Some(checksums)
-    }
-    if let Some(include_in_classpath) = partial.include_in_classpath {
-        merge.include_in_classpath = include_in_classpath
-    }
 
-    merge
-}
+        if let Ok(path) = crate::get_path_from_artifact(&self.name) {
+            let repos = self
+                .url
+                .as_deref()
+                .into_iter()
+                .chain(extra_repos.iter().copied())
+                .filter(|x| !x.is_empty());
+
+            for repo in repos {
+                let url = repository_url(repo, &path);
+                if !candidates.contains(&url) {
+                    candidates.push(url);
+                }
+            }
+        }
+
+        candidates
+    }
+}
+
+/// Joins the base URL of a maven repository and the path of an artifact in it
+fn repository_url(repo: &str, path: &str) -> String {
+    if repo.ends_with('/') {
+        format!("{}{}", repo, path)
+    } else {
+        format!("{}/{}", repo, path)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The replacement of a library, such as a patched build of it
+pub enum LibraryOverride {
+    /// The library is downloaded from somewhere else, and stored at the path
+    /// of the download in the libraries directory
+    Download(LibraryDownload),
+    /// The library is a file on disk which isn't downloaded
+    Path(PathBuf),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A replacement of a library along with whether it adds a library
+struct OverrideEntry {
+    replacement: LibraryOverride,
+    additive: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// Replacements of libraries by their `groupId:artifactId`, consulted before
+/// the library itself is resolved. An override always wins over the
+/// artifact of the version's library, whatever version of it the version
+/// uses
+pub struct LibraryOverrides {
+    entries: HashMap<String, OverrideEntry>,
+}
+
+impl LibraryOverrides {
+    /// Creates an empty set of overrides
+    pub fn new() -> LibraryOverrides {
+        LibraryOverrides::default()
+    }
+
+    /// Replaces the library with the `groupId:artifactId`, which is expected
+    /// to be a library of the version
+    pub fn insert(&mut self, coordinate: &str, replacement: LibraryOverride) {
+        self.entries.insert(
+            coordinate.to_string(),
+            OverrideEntry {
+                replacement,
+                additive: false,
+            },
+        );
+    }
+
+    /// Adds a library with the `groupId:artifactId`, which the version is
+    /// not expected to have. If it does, its library is replaced
+    pub fn insert_additive(
+        &mut self,
+        coordinate: &str,
+        replacement: LibraryOverride,
+    ) {
+        self.entries.insert(
+            coordinate.to_string(),
+            OverrideEntry {
+                replacement,
+                additive: true,
+            },
+        );
+    }
+
+    /// Returns the override of the library, if there is one
+    pub fn get(&self, library: &Library) -> Option<&LibraryOverride> {
+        let coordinate = crate::maven_ga(&library.name).ok()?;
+
+        self.entries.get(&coordinate).map(|x| &x.replacement)
+    }
+
+    /// Returns the additive overrides, which are libraries to add
+    pub fn additive(&self) -> impl Iterator<Item = &LibraryOverride> {
+        self.entries
+            .values()
+            .filter(|x| x.additive)
+            .map(|x| &x.replacement)
+    }
+
+    /// Checks that every override which isn't additive replaces a library of
+    /// the version, failing with [`Error::UnmatchedLibraryOverride`]
+    /// otherwise
+    pub fn validate(&self, info: &VersionInfo) -> Result<(), Error> {
+        let coordinates = info
+            .libraries
+            .iter()
+            .filter_map(|x| crate::maven_ga(&x.name).ok())
+            .collect::<HashSet<_>>();
+
+        let mut unmatched = self
+            .entries
+            .iter()
+            .filter(|(coordinate, entry)| {
+                !entry.additive && !coordinates.contains(*coordinate)
+            })
+            .map(|(coordinate, _)| coordinate)
+            .collect::<Vec<_>>();
+        unmatched.sort();
+
+        match unmatched.first() {
+            Some(coordinate) => Err(Error::UnmatchedLibraryOverride {
+                coordinate: coordinate.to_string(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the download of the primary artifact of the library like
+    /// [`Library::resolve_download`], unless it is overridden. Libraries
+    /// replaced by a file on disk have nothing to download
+    pub fn resolve_download(
+        &self,
+        library: &Library,
+        default_repo: &str,
+    ) -> Option<LibraryDownload> {
+        match self.get(library) {
+            Some(LibraryOverride::Download(download)) => Some(download.clone()),
+            Some(LibraryOverride::Path(_)) => None,
+            None => library.resolve_download(default_repo),
+        }
+    }
+
+    /// Returns the path of the primary artifact of the library in the
+    /// libraries directory, unless it is overridden
+    pub fn library_path(
+        &self,
+        library: &Library,
+        libraries_dir: &Path,
+    ) -> Result<PathBuf, Error> {
+        match self.get(library) {
+            Some(replacement) => {
+                Ok(LibraryOverrides::path(replacement, libraries_dir))
+            }
+            None => Ok(libraries_dir
+                .join(crate::get_path_from_artifact(&library.name)?)),
+        }
+    }
+
+    /// Returns the path of the replacement in the libraries directory
+    fn path(replacement: &LibraryOverride, libraries_dir: &Path) -> PathBuf {
+        match replacement {
+            LibraryOverride::Download(download) => {
+                libraries_dir.join(&download.path)
+            }
+            LibraryOverride::Path(path) => path.clone(),
+        }
+    }
+
+    /// Returns the paths of the additive overrides in the libraries directory,
+    /// sorted so they are placed deterministically
+    pub fn additive_paths(&self, libraries_dir: &Path) -> Vec<PathBuf> {
+        let mut paths = self
+            .additive()
+            .map(|x| LibraryOverrides::path(x, libraries_dir))
+            .collect::<Vec<_>>();
+        paths.sort();
+
+        paths
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+/// A partial library which should be merged with a full library
+pub struct PartialLibrary {
+    /// The files the library has
+    pub downloads: Option<LibraryDownloads>,
+    /// Rules of the extraction of the file
+    pub extract: Option<LibraryExtract>,
+    /// The maven name of the library. The format is `groupId:artifactId:version`
+    pub name: Option<String>,
+    /// The URL to the repository where the library can be downloaded
+    pub url: Option<String>,
+    /// Native files that the library relies on
+    pub natives: Option<Map<Os, String>>,
+    /// Rules deciding whether the library should be downloaded or not
+    pub rules: Option<Vec<Rule>>,
+    /// SHA1 Checksums for validating the library's integrity. Only present for forge libraries
+    pub checksums: Option<Vec<String>>,
+    /// Whether the library should be included in the classpath at the game's launch
+    pub include_in_classpath: Option<bool>,
+}
+
+impl PartialLibrary {
+    /// Converts the partial library into a full library, for a partial library
+    /// which is a new library instead of overriding an existing one. Fields
+    /// which aren't set are left empty, except `include_in_classpath` which
+    /// defaults to `true`. Fails if the name of the library isn't set
+    pub fn into_library(self) -> Result<Library, Error> {
+        Ok(Library {
+            downloads: self.downloads,
+            extract: self.extract,
+            name: self.name.ok_or_else(|| {
+                Error::ParseError(
+                    "Unable to find name for partial library".to_string(),
+                )
+            })?,
+            url: self.url,
+            natives: self.natives,
+            rules: self.rules,
+            checksums: self.checksums,
+            include_in_classpath: self
+                .include_in_classpath
+                .unwrap_or_else(default_include_in_classpath),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+/// How the collections of a partial library are combined with the ones of the
+/// library it is merged into by [`merge_partial_library_with_strategy`]
+pub enum MergeStrategy {
+    /// The rules of the partial library are appended to the ones of the
+    /// library, and its natives and classifiers are added to the ones of the
+    /// library, replacing the entries with the same key
+    #[default]
+    Append,
+    /// The rules, natives and classifiers of the partial library replace the
+    /// ones of the library entirely, if the partial library has them
+    Replace,
+}
+
+/// Merges a partial library to make a complete library, with the
+/// [`MergeStrategy::Append`] strategy
+pub fn merge_partial_library(
+    partial: PartialLibrary,
+    merge: Library,
+) -> Library {
+    merge_partial_library_with_strategy(partial, merge, MergeStrategy::Append)
+}
+
+/// Merges a partial library to make a complete library. The rules, natives
+/// and classifiers are combined according to the strategy, every other field
+/// the partial library has replaces the one of the library
+pub fn merge_partial_library_with_strategy(
+    partial: PartialLibrary,
+    mut merge: Library,
+    strategy: MergeStrategy,
+) -> Library {
+    if let Some(downloads) = partial.downloads {
+        if let Some(merge_downloads) = &mut merge.downloads {
+            if let Some(artifact) = downloads.artifact {
+                merge_downloads.artifact = Some(artifact);
+            }
+            if let Some(classifiers) = downloads.classifiers {
+                match (&mut merge_downloads.classifiers, strategy) {
+                    (Some(merge_classifiers), MergeStrategy::Append) => {
+                        for classifier in classifiers {
+                            merge_classifiers
+                                .insert(classifier.0, classifier.1);
+                        }
+                    }
+                    _ => merge_downloads.classifiers = Some(classifiers),
+                }
+            }
+        } else {
+            merge.downloads = Some(downloads)
+        }
+    }
+    if let Some(extract) = partial.extract {
+        merge.extract = Some(extract)
+    }
+    if let Some(name) = partial.name {
+        merge.name = name
+    }
+    if let Some(url) = partial.url {
+        merge.url = Some(url)
+    }
+    if let Some(natives) = partial.natives {
+        match (&mut merge.natives, strategy) {
+            (Some(merge_natives), MergeStrategy::Append) => {
+                for native in natives {
+                    merge_natives.insert(native.0, native.1);
+                }
+            }
+            _ => merge.natives = Some(natives),
+        }
+    }
+    if let Some(rules) = partial.rules {
+        match (&mut merge.rules, strategy) {
+            (Some(merge_rules), MergeStrategy::Append) => {
+                merge_rules.extend(rules)
+            }
+            _ => merge.rules = Some(rules),
+        }
+    }
+    if let Some(checksums) = partial.checksums {
+        merge.checksums = Some(checksums)
+    }
+    if let Some(include_in_classpath) = partial.include_in_classpath {
+        merge.include_in_classpath = include_in_classpath
+    }
+
+    merge
+}
 
 fn default_include_in_classpath() -> bool {
     true
 }
 
+fn is_default_include_in_classpath(include_in_classpath: &bool) -> bool {
+    *include_in_classpath
+}
+
 #[cfg_attr(feature = "bincode", derive(Encode, Decode))]
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(untagged)]
 /// A container for an argument or multiple arguments
 pub enum ArgumentValue {
@@ -447,7 +1455,7 @@ pub enum ArgumentValue {
 }
 
 #[cfg_attr(feature = "bincode", derive(Encode, Decode))]
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(untagged)]
 /// A command line argument passed to a program
 pub enum Argument {
@@ -455,7 +1463,9 @@ pub enum Argument {
     Normal(String),
     /// An argument which is only applied if certain conditions are met
     Ruled {
-        /// The rules deciding whether the argument(s) is used or not
+        #[serde(default)]
+        /// The rules deciding whether the argument(s) is used or not, a
+        /// missing list always applies
         rules: Vec<Rule>,
         /// The container of the argument(s) that should be applied accordingly
         value: ArgumentValue,
@@ -473,29 +1483,151 @@ pub enum ArgumentType {
     Jvm,
 }
 
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Copy)]
+/// A placeholder in the arguments of a version, written as `${name}`, which is
+/// substituted by the launcher
+pub enum Placeholder {
+    /// The directory the natives of the version are extracted to
+    NativesDirectory,
+    /// The name of the launcher
+    LauncherName,
+    /// The version of the launcher
+    LauncherVersion,
+    /// The classpath of the game
+    Classpath,
+    /// The name of the player
+    AuthPlayerName,
+    /// The version ID of the version
+    VersionName,
+    /// The directory the game runs in
+    GameDirectory,
+    /// The directory assets are stored in
+    AssetsRoot,
+    /// The version ID of the assets
+    AssetsIndexName,
+    /// The UUID of the player
+    AuthUuid,
+    /// The access token of the player
+    AuthAccessToken,
+    /// The type of the player's account
+    UserType,
+    /// The type of the version
+    VersionType,
+    /// The separator of the entries of the classpath on the OS
+    ClasspathSeparator,
+    /// The directory libraries are stored in
+    LibraryDirectory,
+}
+
+impl Placeholder {
+    /// All known placeholders
+    pub const ALL: &'static [Placeholder] = &[
+        Placeholder::NativesDirectory,
+        Placeholder::LauncherName,
+        Placeholder::LauncherVersion,
+        Placeholder::Classpath,
+        Placeholder::AuthPlayerName,
+        Placeholder::VersionName,
+        Placeholder::GameDirectory,
+        Placeholder::AssetsRoot,
+        Placeholder::AssetsIndexName,
+        Placeholder::AuthUuid,
+        Placeholder::AuthAccessToken,
+        Placeholder::UserType,
+        Placeholder::VersionType,
+        Placeholder::ClasspathSeparator,
+        Placeholder::LibraryDirectory,
+    ];
+
+    /// Converts the placeholder to its name, without the surrounding `${}`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Placeholder::NativesDirectory => "natives_directory",
+            Placeholder::LauncherName => "launcher_name",
+            Placeholder::LauncherVersion => "launcher_version",
+            Placeholder::Classpath => "classpath",
+            Placeholder::AuthPlayerName => "auth_player_name",
+            Placeholder::VersionName => "version_name",
+            Placeholder::GameDirectory => "game_directory",
+            Placeholder::AssetsRoot => "assets_root",
+            Placeholder::AssetsIndexName => "assets_index_name",
+            Placeholder::AuthUuid => "auth_uuid",
+            Placeholder::AuthAccessToken => "auth_access_token",
+            Placeholder::UserType => "user_type",
+            Placeholder::VersionType => "version_type",
+            Placeholder::ClasspathSeparator => "classpath_separator",
+            Placeholder::LibraryDirectory => "library_directory",
+        }
+    }
+}
+
+impl TryFrom<&str> for Placeholder {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Placeholder::ALL
+            .iter()
+            .find(|x| x.as_str() == value)
+            .copied()
+            .ok_or_else(|| {
+                Error::ParseError(format!("Unknown placeholder {}", value))
+            })
+    }
+}
+
+/// Adds the known placeholders referenced in the input to the set
+fn collect_placeholders(input: &str, placeholders: &mut HashSet<Placeholder>) {
+    let mut rest = input;
+
+    while let Some(start) = rest.find("${") {
+        rest = &rest[start + 2..];
+
+        if let Some(end) = rest.find('}') {
+            if let Ok(placeholder) = Placeholder::try_from(&rest[..end]) {
+                placeholders.insert(placeholder);
+            }
+            rest = &rest[end + 1..];
+        } else {
+            break;
+        }
+    }
+}
+
 #[cfg_attr(feature = "bincode", derive(Encode, Decode))]
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
-/// Information about a version
+/// Information about a version.
+/// Versions are only equal if all of their fields are, including their times,
+/// while the order of entries in their maps is irrelevant
 pub struct VersionInfo {
     #[serde(skip_serializing_if = "Option::is_none")]
     /// Arguments passed to the game or JVM
-    pub arguments: Option<HashMap<ArgumentType, Vec<Argument>>>,
-    /// Assets for the game
+    pub arguments: Option<Map<ArgumentType, Vec<Argument>>>,
+    /// The assets index of the game. This is the canonical source of the assets
+    /// index, versions of the GDLauncher mirror may provide another one, see
+    /// [`Version::assets_index_download`]
     pub asset_index: AssetIndex,
     /// The version ID of the assets
     pub assets: String,
+    #[serde(default)]
+    /// Whether the version supports the latest player safety features, see
+    /// [`Version::compliance_level`]. This is `0` for versions without it
+    pub compliance_level: u32,
     /// Game downloads of the version
-    pub downloads: HashMap<DownloadType, Download>,
+    pub downloads: Map<DownloadType, Download>,
     /// The version ID of the version
     pub id: String,
-
+    #[serde(skip_serializing_if = "Option::is_none")]
     /// The Java version this version supports
     pub java_version: Option<JavaVersion>,
     /// Libraries that the version depends on
     pub libraries: Vec<Library>,
     /// The classpath to the main class to launch the game
     pub main_class: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// (Modded-only) The main class of the version a partial version was
+    /// merged into, if the partial version replaced it with its own
+    pub inherited_main_class: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     /// (Legacy) Arguments passed to the game
     pub minecraft_arguments: Option<String>,
@@ -512,19 +1644,958 @@ pub struct VersionInfo {
     pub type_: VersionType,
     #[serde(skip_serializing_if = "Option::is_none")]
     /// (Forge-only)
-    pub data: Option<HashMap<String, SidedDataEntry>>,
+    pub data: Option<Map<String, SidedDataEntry>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     /// (Forge-only) The list of processors to run after downloading the files
     pub processors: Option<Vec<Processor>>,
+    #[serde(flatten)]
+    /// Fields which aren't modelled, such as `_comment_`, preserved so they
+    /// are serialized again
+    pub extra: UnknownFields,
+}
+
+#[derive(Debug, Clone, Copy)]
+/// A view of a version with what is needed to install and launch one of its
+/// sides, see [`VersionInfo::for_side`]
+pub struct SidedVersionInfo<'a> {
+    /// The version
+    pub info: &'a VersionInfo,
+    /// The side of the installation
+    pub side: Side,
+}
+
+impl<'a> SidedVersionInfo<'a> {
+    /// The game jar of the side
+    pub fn jar_download(&self) -> Option<&'a Download> {
+        self.info.downloads.get(&match self.side {
+            Side::Client => DownloadType::Client,
+            Side::Server => DownloadType::Server,
+        })
+    }
+
+    /// The obfuscation mappings of the game jar of the side
+    pub fn mappings_download(&self) -> Option<&'a Download> {
+        self.info.downloads.get(&match self.side {
+            Side::Client => DownloadType::ClientMappings,
+            Side::Server => DownloadType::ServerMappings,
+        })
+    }
+
+    /// Whether the side needs the assets, which only the client does
+    pub fn needs_assets(&self) -> bool {
+        self.side == Side::Client
+    }
+
+    /// Whether the side needs natives to be extracted, which only the client
+    /// does, the libraries of the server are bundled in its jar
+    pub fn needs_natives(&self) -> bool {
+        self.side == Side::Client
+    }
+
+    /// The arguments of the side. Only the client has arguments, the server
+    /// is launched with its jar and takes no placeholders
+    pub fn arguments(&self) -> Option<&'a Map<ArgumentType, Vec<Argument>>> {
+        match self.side {
+            Side::Client => self.info.arguments.as_ref(),
+            Side::Server => None,
+        }
+    }
+
+    /// The processors to run for the side, which are the ones listing it in
+    /// their `sides` or listing no sides at all
+    pub fn processors(&self) -> impl Iterator<Item = &'a Processor> + 'a {
+        let side = self.side;
+
+        self.info.processors.iter().flatten().filter(move |x| {
+            x.sides
+                .as_ref()
+                .is_none_or(|sides| sides.iter().any(|x| x == side.as_str()))
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A file bundled in a server jar, see [`BundlerManifest`]
+pub struct BundledFile {
+    /// The SHA256 hash of the file
+    pub sha256: String,
+    /// The ID of the file, the maven name for libraries
+    pub id: String,
+    /// The path of the file, relative to the `META-INF/versions/` or
+    /// `META-INF/libraries/` directory of the jar
+    pub path: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// The contents of a bundler server jar, which the jars of 1.18 and later
+/// servers are. These bundle the real server jar and its libraries, which they
+/// extract before launching it
+pub struct BundlerManifest {
+    /// The bundled server jars, from `META-INF/versions.list`
+    pub versions: Vec<BundledFile>,
+    /// The bundled libraries, from `META-INF/libraries.list`
+    pub libraries: Vec<BundledFile>,
+    /// The main class of the bundled server, from `META-INF/main-class`
+    pub main_class: Option<String>,
+}
+
+/// Reads the contents of a bundler server jar. Fails if the jar has no
+/// `META-INF/versions.list`, as is the case for servers before 1.18
+#[cfg(feature = "jar")]
+pub fn parse_server_bundler(bytes: &[u8]) -> Result<BundlerManifest, Error> {
+    use std::io::Read;
+
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes))?;
+    let mut read = |name: &str| -> Result<Option<String>, Error> {
+        let mut file = match archive.by_name(name) {
+            Ok(file) => file,
+            Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+
+        Ok(Some(contents))
+    };
+
+    // Each line of the lists is `<sha256>\t<id>\t<path>`
+    fn parse_list(list: &str) -> Result<Vec<BundledFile>, Error> {
+        list.lines()
+            .filter(|x| !x.trim().is_empty())
+            .map(|line| match line.split('\t').collect::<Vec<_>>()[..] {
+                [sha256, id, path] => Ok(BundledFile {
+                    sha256: sha256.to_string(),
+                    id: id.to_string(),
+                    path: path.to_string(),
+                }),
+                _ => Err(Error::ParseError(format!(
+                    "Invalid bundler list entry {}",
+                    line
+                ))),
+            })
+            .collect()
+    }
+
+    let versions = read("META-INF/versions.list")?.ok_or_else(|| {
+        Error::ParseError("The jar is not a bundler jar".to_string())
+    })?;
+    let libraries = read("META-INF/libraries.list")?.unwrap_or_default();
+    let main_class = read("META-INF/main-class")?
+        .map(|x| x.trim().to_string())
+        .filter(|x| !x.is_empty());
+
+    Ok(BundlerManifest {
+        versions: parse_list(&versions)?,
+        libraries: parse_list(&libraries)?,
+        main_class,
+    })
+}
+
+/// The `minimum_launcher_version` which introduced the `arguments` map, replacing
+/// the legacy `minecraft_arguments` string
+pub const ARGUMENTS_LAUNCHER_VERSION: u32 = 21;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// A mitigation of the Log4Shell vulnerability of old versions, see
+/// [`VersionInfo::log4j_mitigation`]
+pub enum Log4jMitigation {
+    /// Disabling message lookups with a system property
+    NoLookups,
+    /// Replacing the logging configuration with a patched one published by
+    /// Mojang, which has to be downloaded
+    Config {
+        /// The file name Mojang publishes the configuration under
+        file_name: &'static str,
+        /// The URL of the configuration
+        url: &'static str,
+        /// The SHA1 hash of the configuration
+        sha1: &'static str,
+    },
+}
+
+impl Log4jMitigation {
+    /// Returns the JVM argument applying the mitigation, given the path the
+    /// patched configuration was downloaded to, if it needs one
+    pub fn jvm_argument(&self, config_path: &Path) -> String {
+        match self {
+            Log4jMitigation::NoLookups => {
+                "-Dlog4j2.formatMsgNoLookups=true".to_string()
+            }
+            Log4jMitigation::Config { .. } => format!(
+                "-Dlog4j.configurationFile={}",
+                config_path.to_string_lossy()
+            ),
+        }
+    }
+}
+
+/// The JVM arguments the vanilla launcher passes to versions which only have
+/// the legacy `minecraft_arguments` string, the same as the ones listed by the
+/// first versions with an `arguments` map. The main class is not included and
+/// follows these arguments
+pub fn default_jvm_arguments() -> Vec<Argument> {
+    fn ruled(os: OsRule, value: ArgumentValue) -> Argument {
+        Argument::Ruled {
+            rules: vec![Rule {
+                action: RuleAction::Allow,
+                os: Some(os),
+                features: None,
+            }],
+            value,
+        }
+    }
+
+    vec![
+        ruled(
+            OsRule {
+                name: Some(Os::Osx),
+                version: None,
+                arch: None,
+            },
+            ArgumentValue::Single("-XstartOnFirstThread".to_string()),
+        ),
+        ruled(
+            OsRule {
+                name: Some(Os::Windows),
+                version: None,
+                arch: None,
+            },
+            ArgumentValue::Single(
+                "-XX:HeapDumpPath=MojangTricksIntelDriversForPerformance_javaw.exe_minecraft.exe.heapdump"
+                    .to_string(),
+            ),
+        ),
+        ruled(
+            OsRule {
+                name: Some(Os::Windows),
+                version: Some("^10\\.".to_string()),
+                arch: None,
+            },
+            ArgumentValue::Many(vec![
+                "-Dos.name=Windows 10".to_string(),
+                "-Dos.version=10.0".to_string(),
+            ]),
+        ),
+        ruled(
+            OsRule {
+                name: None,
+                version: None,
+                arch: Some("x86".to_string()),
+            },
+            ArgumentValue::Single("-Xss1M".to_string()),
+        ),
+        Argument::Normal(
+            "-Djava.library.path=${natives_directory}".to_string(),
+        ),
+        Argument::Normal(
+            "-Dminecraft.launcher.brand=${launcher_name}".to_string(),
+        ),
+        Argument::Normal(
+            "-Dminecraft.launcher.version=${launcher_version}".to_string(),
+        ),
+        Argument::Normal("-cp".to_string()),
+        Argument::Normal("${classpath}".to_string()),
+    ]
+}
+
+impl VersionInfo {
+    /// Deserializes a version from a reader, such as a buffered file, without
+    /// reading it into memory first
+    pub fn from_reader<R: std::io::Read>(
+        reader: R,
+    ) -> Result<VersionInfo, Error> {
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    /// Deserializes a version, failing with [`Error::UnknownField`] if it has
+    /// a field which isn't modelled, to notice when the format changes
+    pub fn from_slice_strict(bytes: &[u8]) -> Result<VersionInfo, Error> {
+        let info: VersionInfo = parse_json_strict(bytes)?;
+
+        match info.extra.keys().next() {
+            Some(field) => Err(Error::UnknownField {
+                field: field.clone(),
+                type_name: std::any::type_name::<VersionInfo>(),
+            }),
+            None => Ok(info),
+        }
+    }
+
+    /// Returns the known placeholders referenced by the arguments of this version
+    pub fn placeholders(&self) -> HashSet<Placeholder> {
+        let mut placeholders = HashSet::new();
+
+        if let Some(arguments) = &self.arguments {
+            for argument in arguments.values().flatten() {
+                match argument {
+                    Argument::Normal(value) => {
+                        collect_placeholders(value, &mut placeholders)
+                    }
+                    Argument::Ruled { value, .. } => match value {
+                        ArgumentValue::Single(value) => {
+                            collect_placeholders(value, &mut placeholders)
+                        }
+                        ArgumentValue::Many(values) => {
+                            for value in values {
+                                collect_placeholders(value, &mut placeholders)
+                            }
+                        }
+                    },
+                }
+            }
+        }
+        if let Some(minecraft_arguments) = &self.minecraft_arguments {
+            collect_placeholders(minecraft_arguments, &mut placeholders);
+        }
+
+        placeholders
+    }
+
+    /// Whether this version requires a newer launcher than one supporting up to
+    /// the `supported` launcher version.
+    /// See [`ARGUMENTS_LAUNCHER_VERSION`] for the version which introduced the
+    /// `arguments` map
+    pub fn requires_launcher_version(&self, supported: u32) -> bool {
+        self.minimum_launcher_version > supported
+    }
+
+    /// Checks that a launcher supporting up to the `supported` launcher version
+    /// is able to launch this version
+    pub fn check_launcher_version(&self, supported: u32) -> Result<(), Error> {
+        if self.requires_launcher_version(supported) {
+            return Err(Error::UnsupportedLauncherVersion {
+                required: self.minimum_launcher_version,
+                supported,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Returns the path the asset index of the version is saved to, which is
+    /// `indexes/<assets>.json` in the assets directory
+    pub fn asset_index_path(&self, assets_dir: &Path) -> PathBuf {
+        assets_dir
+            .join("indexes")
+            .join(format!("{}.json", self.assets))
+    }
+
+    /// Checks the version for inconsistencies which would break its launch
+    /// without an error otherwise: a library declaring natives whose
+    /// classifier has no download, see [`Library::try_native_download`]
+    pub fn validate(&self) -> Result<(), Error> {
+        for library in &self.libraries {
+            let mut oses = library
+                .natives
+                .iter()
+                .flat_map(|x| x.keys())
+                .collect::<Vec<_>>();
+            oses.sort_by_cached_key(|x| format!("{:?}", x));
+
+            for os in oses {
+                library.try_native_download(os)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns how the assets of the version are laid out on disk, derived from
+    /// the ID of its asset index. [`AssetsIndex::layout`] derives it from the
+    /// flags of the fetched index instead
+    pub fn asset_layout(&self) -> AssetLayout {
+        match &*self.assets {
+            "pre-1.6" => AssetLayout::PreOneSix,
+            "legacy" => AssetLayout::Legacy,
+            _ => AssetLayout::Modern,
+        }
+    }
+
+    /// Returns the native library archives needed on the OS with the given
+    /// features, which need to be extracted into the natives directory, minus
+    /// the entries excluded by their extraction rules. Libraries whose natives
+    /// have no download are skipped, [`VersionInfo::validate`] reports them
+    pub fn natives_to_extract(
+        &self,
+        os: &Os,
+        features: &FeatureSet,
+        natives_dir: &Path,
+    ) -> Vec<NativeExtraction> {
+        self.libraries
+            .iter()
+            .filter(|x| x.applies(os, features))
+            .filter_map(|library| {
+                Some(NativeExtraction {
+                    name: library.name.clone(),
+                    classifier: library.native_classifier(os)?,
+                    download: library.native_download(os)?.clone(),
+                    extract: library
+                        .extract
+                        .clone()
+                        .unwrap_or(LibraryExtract { exclude: None }),
+                    destination: natives_dir.to_path_buf(),
+                })
+            })
+            .collect()
+    }
+
+    /// Converts the legacy `minecraft_arguments` string into game arguments of
+    /// the `arguments` map and clears it, so the arguments of every version
+    /// can be handled the same way. The legacy arguments come before any game
+    /// arguments already in the map. As the legacy string has no rules, every
+    /// argument becomes an unconditional [`Argument::Normal`], including ones
+    /// which were only applied in some cases. If the map has no JVM arguments,
+    /// the [`default_jvm_arguments`] are added
+    pub fn upgrade_legacy_arguments(&mut self) {
+        let minecraft_arguments = match self.minecraft_arguments.take() {
+            Some(minecraft_arguments) => minecraft_arguments,
+            None => return,
+        };
+
+        let arguments = self.arguments.get_or_insert_with(Map::new);
+        let game = arguments.entry(ArgumentType::Game).or_default();
+        let existing = std::mem::take(game);
+        game.extend(
+            minecraft_arguments
+                .split_whitespace()
+                .map(|x| Argument::Normal(x.to_string()))
+                .chain(existing),
+        );
+
+        arguments
+            .entry(ArgumentType::Jvm)
+            .or_insert_with(default_jvm_arguments);
+    }
+
+    /// Removes duplicate flags from the arguments, such as those introduced by
+    /// merging a partial version into its base, keeping the last occurrence,
+    /// which is the one of the partial version. Only unconditional arguments
+    /// are considered, arguments with rules are left as they are.
+    ///
+    /// Game arguments of the form `--flag value` are keyed by their flag. As
+    /// multiple `--tweakClass` flags are valid, these are only removed if both
+    /// their flag and value are duplicates. JVM arguments of the form
+    /// `-Dproperty=value` are keyed by their property. Every other argument,
+    /// such as a game argument without a value or a JVM flag which may be
+    /// repeated like `--add-opens`, is positional and always kept
+    pub fn dedup_arguments(&mut self) {
+        if let Some(arguments) = &mut self.arguments {
+            for (type_, arguments) in arguments.iter_mut() {
+                *arguments = dedup_arguments(std::mem::take(arguments), *type_);
+            }
+        }
+    }
+
+    /// Returns the main class of the base version and the one of the mod
+    /// loader replacing it, if a merged partial version replaced it
+    pub fn main_class_override(&self) -> Option<(&str, &str)> {
+        self.inherited_main_class
+            .as_deref()
+            .map(|inherited| (inherited, &*self.main_class))
+    }
+
+    /// Returns the Java version the version needs: its explicit Java version,
+    /// or else the one the versions released at the same time need. Versions
+    /// from before the 1.17 snapshots need Java 8, those up to 1.18 need
+    /// Java 16, those up to the 1.20.5 snapshots need Java 17 and later ones
+    /// need Java 21
+    pub fn effective_java_version(&self) -> JavaVersion {
+        if let Some(java_version) = &self.java_version {
+            return java_version.clone();
+        }
+
+        // The release times of 21w19a, 1.18-pre2 and 24w14a, the first
+        // versions needing a newer Java
+        let eras = [
+            ((2021, 5, 12), MinecraftJavaProfile::JavaRuntimeAlpha, 16),
+            ((2021, 11, 16), MinecraftJavaProfile::JavaRuntimeGamma, 17),
+            ((2024, 4, 3), MinecraftJavaProfile::JavaRuntimeDelta, 21),
+        ];
+        let (profile, major_version) = eras
+            .iter()
+            .rev()
+            .find(|((year, month, day), ..)| {
+                Utc.with_ymd_and_hms(*year, *month, *day, 0, 0, 0)
+                    .single()
+                    .is_some_and(|x| self.release_time >= x)
+            })
+            .map(|(_, profile, major_version)| {
+                (profile.clone(), *major_version)
+            })
+            .unwrap_or((MinecraftJavaProfile::JRELegacy, 8));
+
+        JavaVersion {
+            component: profile.as_str().to_string(),
+            major_version,
+        }
+    }
+
+    /// Returns the major and minor version of the log4j library of the
+    /// version, if it has one
+    fn log4j_version(&self) -> Option<(u32, u32)> {
+        let version = self.libraries.iter().find_map(|x| {
+            x.name.strip_prefix("org.apache.logging.log4j:log4j-core:")
+        })?;
+
+        let mut parts = version.split('.');
+        let major = parts.next()?.parse().ok()?;
+        // Beta versions have a suffix on the minor version, as in `2.0-beta9`
+        let minor = parts
+            .next()?
+            .split(|x: char| !x.is_ascii_digit())
+            .next()?
+            .parse()
+            .ok()?;
+
+        Some((major, minor))
+    }
+
+    /// Whether the version bundles a log4j version vulnerable to Log4Shell,
+    /// which are the 2.x versions before 2.15, as shipped by the versions from
+    /// 1.7 up to 1.18. Modded versions are checked by the log4j library they
+    /// inherit. See [`VersionInfo::log4j_mitigation`] for how to protect it
+    pub fn needs_log4j_mitigation(&self) -> bool {
+        self.log4j_version()
+            .is_some_and(|(major, minor)| major == 2 && minor < 15)
+    }
+
+    /// Returns the mitigation of Log4Shell Mojang recommends for the log4j
+    /// version the version bundles, if it needs one
+    pub fn log4j_mitigation(&self) -> Option<Log4jMitigation> {
+        if !self.needs_log4j_mitigation() {
+            return None;
+        }
+
+        Some(match self.log4j_version()? {
+            // Versions since 2.10 support disabling lookups
+            (_, minor) if minor >= 10 => Log4jMitigation::NoLookups,
+            // 1.7 to 1.11.2 bundle 2.0-beta9
+            (_, 0) => Log4jMitigation::Config {
+                file_name: "log4j2_17-111.xml",
+                url: "https://launcher.mojang.com/v1/objects/4bb89a97a66f350bc9f73b3ca8509632682aea2e/log4j2_17-111.xml",
+                sha1: "4bb89a97a66f350bc9f73b3ca8509632682aea2e",
+            },
+            // 1.12 to 1.16.5 bundle 2.8.1
+            _ => Log4jMitigation::Config {
+                file_name: "log4j2_112-116.xml",
+                url: "https://launcher.mojang.com/v1/objects/02937d122c86ce73319ef9975b58896fc1b491d1/log4j2_112-116.xml",
+                sha1: "02937d122c86ce73319ef9975b58896fc1b491d1",
+            },
+        })
+    }
+
+    /// Returns a view of the version with what is needed for the side
+    pub fn for_side(&self, side: Side) -> SidedVersionInfo<'_> {
+        SidedVersionInfo { info: self, side }
+    }
+
+    /// Returns every OS any library of the version has natives for
+    pub fn native_platforms(&self) -> HashSet<Os> {
+        self.libraries
+            .iter()
+            .filter_map(|x| x.natives.as_ref())
+            .flat_map(|x| x.keys().cloned())
+            .collect()
+    }
+
+    /// Returns a SHA1 hash of the content of the version, for keying data
+    /// derived from it. It is the hash of the canonical JSON serialization of
+    /// the version, with the keys of every object sorted, so it is the same
+    /// across runs and machines even though the version contains hash maps
+    pub fn content_hash(&self) -> Result<String, Error> {
+        let mut value = serde_json::to_value(self)?;
+        value.sort_all_objects();
+
+        Ok(sha1::Sha1::from(serde_json::to_vec(&value)?).hexdigest())
+    }
+
+    /// Converts the version into a `net.minecraft` component of the
+    /// Prism Launcher/MultiMC metadata format. The LWJGL libraries are kept in
+    /// the component instead of being split into their own component, so the
+    /// component neither requires nor conflicts with other components. The
+    /// components of mod loaders, which require this one, are converted with
+    /// [`PartialVersionInfo::to_prism_component`](crate::modded::PartialVersionInfo::to_prism_component)
+    pub fn to_prism_component(&self) -> Result<String, Error> {
+        // Modern versions only have structured arguments, which the format
+        // represents as a legacy argument string and traits
+        let minecraft_arguments =
+            self.minecraft_arguments.clone().or_else(|| {
+                self.arguments.as_ref().map(|arguments| {
+                    arguments
+                        .get(&ArgumentType::Game)
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|x| match x {
+                            Argument::Normal(value) => Some(&**value),
+                            Argument::Ruled { .. } => None,
+                        })
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                })
+            });
+        let starts_on_first_thread = self
+            .arguments
+            .as_ref()
+            .and_then(|x| x.get(&ArgumentType::Jvm))
+            .into_iter()
+            .flatten()
+            .any(|x| match x {
+                Argument::Normal(value) => value == "-XstartOnFirstThread",
+                Argument::Ruled { value, .. } => match value {
+                    ArgumentValue::Single(value) => {
+                        value == "-XstartOnFirstThread"
+                    }
+                    ArgumentValue::Many(values) => {
+                        values.iter().any(|x| x == "-XstartOnFirstThread")
+                    }
+                },
+            });
+
+        let libraries =
+            self.libraries.iter().map(prism_library).collect::<Vec<_>>();
+
+        let mut component = serde_json::Map::new();
+        component.insert("formatVersion".to_string(), 1.into());
+        component.insert("name".to_string(), "Minecraft".into());
+        component.insert("uid".to_string(), "net.minecraft".into());
+        component.insert("version".to_string(), self.id.clone().into());
+        component.insert("type".to_string(), self.type_.as_str().into());
+        component.insert("order".to_string(), (-2).into());
+        component.insert(
+            "releaseTime".to_string(),
+            serde_json::to_value(self.release_time)?,
+        );
+        component
+            .insert("mainClass".to_string(), self.main_class.clone().into());
+        component.insert(
+            "assetIndex".to_string(),
+            serde_json::to_value(&self.asset_index)?,
+        );
+        component.insert("libraries".to_string(), libraries.into());
+
+        if let Some(client) = self.downloads.get(&DownloadType::Client) {
+            component.insert(
+                "mainJar".to_string(),
+                serde_json::json!({
+                    "name": format!("com.mojang:minecraft:{}:client", self.id),
+                    "downloads": { "artifact": client },
+                }),
+            );
+        }
+        if let Some(minecraft_arguments) = minecraft_arguments {
+            component.insert(
+                "minecraftArguments".to_string(),
+                minecraft_arguments.into(),
+            );
+        }
+        if let Some(java_version) = &self.java_version {
+            component.insert(
+                "compatibleJavaMajors".to_string(),
+                serde_json::json!([java_version.major_version]),
+            );
+        }
+        if starts_on_first_thread {
+            component.insert(
+                "+traits".to_string(),
+                serde_json::json!(["FirstThreadOnMacOS"]),
+            );
+        }
+
+        Ok(serde_json::to_string_pretty(&component)?)
+    }
+}
+
+/// Returns the path of a natives archive in the libraries directory: the
+/// path of its download, which doesn't always match the path of its maven
+/// artifact, or else the path of the `name:classifier` artifact
+pub(crate) fn native_archive_path(
+    name: &str,
+    classifier: &str,
+    download: &LibraryDownload,
+) -> Result<String, Error> {
+    if download.path.is_empty() {
+        crate::get_path_from_artifact(&format!("{}:{}", name, classifier))
+    } else {
+        Ok(download.path.clone())
+    }
+}
+
+/// Converts a library into a library of the Prism Launcher/MultiMC metadata
+/// format
+pub(crate) fn prism_library(library: &Library) -> serde_json::Value {
+    let mut value = serde_json::json!({
+        "name": library.name,
+        "url": library.url,
+        "downloads": library.downloads,
+        "natives": library.natives,
+        "extract": library.extract,
+        "rules": library.rules,
+    });
+    // The format omits unset fields instead of nulling them
+    if let Some(value) = value.as_object_mut() {
+        value.retain(|_, value| !value.is_null());
+    }
+
+    value
+}
+
+/// Removes the keyed arguments which occur again later, see
+/// [`VersionInfo::dedup_arguments`]
+fn dedup_arguments(
+    arguments: Vec<Argument>,
+    type_: ArgumentType,
+) -> Vec<Argument> {
+    fn normal(argument: Option<&Argument>) -> Option<&str> {
+        match argument {
+            Some(Argument::Normal(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    // Group the arguments into units of a flag and its value
+    let mut units: Vec<(Option<String>, Vec<Argument>)> = Vec::new();
+    let mut arguments = arguments.into_iter().peekable();
+    while let Some(argument) = arguments.next() {
+        let key = match (type_, normal(Some(&argument))) {
+            (ArgumentType::Game, Some(flag)) if flag.starts_with("--") => {
+                match normal(arguments.peek()) {
+                    Some(value) if !value.starts_with("--") => {
+                        if flag == "--tweakClass" {
+                            Some(format!("{} {}", flag, value))
+                        } else {
+                            Some(flag.to_string())
+                        }
+                    }
+                    _ => None,
+                }
+            }
+            (ArgumentType::Jvm, Some(property))
+                if property.starts_with("-D") =>
+            {
+                Some(property.split('=').next().unwrap_or(property).to_string())
+            }
+            _ => None,
+        };
+
+        let mut unit = vec![argument];
+        if type_ == ArgumentType::Game && key.is_some() {
+            unit.extend(arguments.next());
+        }
+        units.push((key, unit));
+    }
+
+    let mut last = HashMap::new();
+    for (index, (key, _)) in units.iter().enumerate() {
+        if let Some(key) = key {
+            last.insert(key.clone(), index);
+        }
+    }
+
+    units
+        .into_iter()
+        .enumerate()
+        .filter(|(index, (key, _))| {
+            key.as_ref().is_none_or(|key| last.get(key) == Some(index))
+        })
+        .flat_map(|(_, (_, unit))| unit)
+        .collect()
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// The Java runtimes needed to run a selection of versions, see
+/// [`required_java_runtimes`]
+pub struct RequiredJavaRuntimes {
+    /// The runtimes of the known profiles, with the highest major Java
+    /// version needed of each profile
+    pub runtimes: HashMap<MinecraftJavaProfile, u32>,
+    /// The runtimes whose component isn't a known profile, by their
+    /// component, with the highest major Java version needed of each
+    pub unknown: HashMap<String, u32>,
+}
+
+/// Returns the Java runtimes needed to run all of the versions by their
+/// profile, with the highest major Java version needed of each profile.
+/// Versions without a Java version need the legacy Java 8 runtime. Components
+/// which aren't a known profile are collected separately instead of failing,
+/// so the known runtimes can still be provisioned
+pub fn required_java_runtimes<'a>(
+    versions: impl IntoIterator<Item = &'a VersionInfo>,
+) -> RequiredJavaRuntimes {
+    fn require<K: Eq + Hash>(
+        runtimes: &mut HashMap<K, u32>,
+        key: K,
+        major: u32,
+    ) {
+        let entry = runtimes.entry(key).or_insert(major);
+        *entry = (*entry).max(major);
+    }
+
+    let mut required = RequiredJavaRuntimes::default();
+
+    for version in versions {
+        match &version.java_version {
+            Some(java_version) => {
+                let major_version = java_version.major_version;
+
+                match MinecraftJavaProfile::try_from(&*java_version.component) {
+                    Ok(profile) => {
+                        require(&mut required.runtimes, profile, major_version)
+                    }
+                    Err(_) => require(
+                        &mut required.unknown,
+                        java_version.component.clone(),
+                        major_version,
+                    ),
+                }
+            }
+            None => require(
+                &mut required.runtimes,
+                MinecraftJavaProfile::JRELegacy,
+                8,
+            ),
+        }
+    }
+
+    required
+}
+
+/// Applies a JSON merge patch (RFC 7386) to a version. Objects in the patch are
+/// merged into the version recursively, a `null` removes the field and any
+/// other value, including arrays, replaces the field. A patch adding libraries
+/// therefore contains the whole list of libraries. Fails if the patched version
+/// isn't a valid version anymore, such as when a required field was removed
+pub fn apply_patch(
+    base: VersionInfo,
+    patch: serde_json::Value,
+) -> Result<VersionInfo, Error> {
+    let mut version = serde_json::to_value(base)?;
+    merge_patch(&mut version, patch);
+
+    Ok(serde_json::from_value(version)?)
+}
+
+fn merge_patch(target: &mut serde_json::Value, patch: serde_json::Value) {
+    match patch {
+        serde_json::Value::Object(patch) => {
+            if !target.is_object() {
+                *target = serde_json::Value::Object(serde_json::Map::new());
+            }
+
+            if let Some(target) = target.as_object_mut() {
+                for (key, value) in patch {
+                    if value.is_null() {
+                        target.remove(&key);
+                    } else {
+                        merge_patch(
+                            target
+                                .entry(key)
+                                .or_insert(serde_json::Value::Null),
+                            value,
+                        );
+                    }
+                }
+            }
+        }
+        patch => *target = patch,
+    }
+}
+
+/// An in-memory cache of version infos, keyed by the ID and hash of their
+/// version in the manifest so a changed version is fetched again
+#[derive(Debug, Default)]
+pub struct VersionCache {
+    versions: Mutex<HashMap<(String, String), Arc<VersionInfo>>>,
+}
+
+impl VersionCache {
+    /// Creates an empty cache
+    pub fn new() -> VersionCache {
+        VersionCache::default()
+    }
+
+    /// Returns the cached info of the version, if its hash is unchanged
+    pub fn get(&self, version: &Version) -> Option<Arc<VersionInfo>> {
+        self.versions
+            .lock()
+            .ok()?
+            .get(&(version.id.clone(), version.sha1.clone()))
+            .cloned()
+    }
+
+    /// Caches the info of the version, returning the cached info
+    pub fn insert(
+        &self,
+        version: &Version,
+        info: VersionInfo,
+    ) -> Arc<VersionInfo> {
+        let info = Arc::new(info);
+
+        if let Ok(mut versions) = self.versions.lock() {
+            versions.insert(
+                (version.id.clone(), version.sha1.clone()),
+                info.clone(),
+            );
+        }
+
+        info
+    }
+
+    /// Removes all versions from the cache
+    pub fn clear(&self) {
+        if let Ok(mut versions) = self.versions.lock() {
+            versions.clear();
+        }
+    }
+}
+
+/// Fetches detailed information about a version from the manifest, reusing the
+/// cached information if the version is unchanged
+pub async fn fetch_version_info_cached(
+    version: &Version,
+    cache: &VersionCache,
+) -> Result<Arc<VersionInfo>, Error> {
+    if let Some(info) = cache.get(version) {
+        return Ok(info);
+    }
+
+    Ok(cache.insert(version, fetch_version_info(version).await?))
 }
 
 /// Fetches detailed information about a version from the manifest
 pub async fn fetch_version_info(
     version: &Version,
 ) -> Result<VersionInfo, Error> {
-    Ok(serde_json::from_slice(
-        &download_file(&version.url, Some(&version.sha1)).await?,
-    )?)
+    Ok(fetch_version_info_raw(version).await?.0)
+}
+
+/// Fetches detailed information about the version with the ID, looking it up
+/// in the version manifest at the URL. If no URL is specified, the default is
+/// used. Fails with [`Error::VersionNotFound`] if the manifest has no such
+/// version
+pub async fn fetch_version_info_by_id(
+    id: &str,
+    manifest_url: Option<&str>,
+) -> Result<VersionInfo, Error> {
+    let manifest = fetch_version_manifest(manifest_url).await?;
+    let version = manifest
+        .versions
+        .iter()
+        .find(|x| x.id == id)
+        .ok_or_else(|| Error::VersionNotFound { id: id.to_string() })?;
+
+    fetch_version_info(version).await
+}
+
+/// Fetches detailed information about a version like [`fetch_version_info`],
+/// returning the bytes it was parsed from alongside it
+pub async fn fetch_version_info_raw(
+    version: &Version,
+) -> Result<(VersionInfo, bytes::Bytes), Error> {
+    // Versions from the v1 manifest don't have a hash to validate against
+    let sha1 = Some(&*version.sha1).filter(|x| !x.is_empty());
+    let bytes = download_metadata(&version.url, sha1).await?;
+
+    Ok((parse_json(&version.url, &bytes)?, bytes))
 }
 
 #[cfg_attr(feature = "bincode", derive(Encode, Decode))]
@@ -534,7 +2605,24 @@ pub struct Asset {
     /// The SHA1 hash of the asset file
     pub hash: String,
     /// The size of the asset file
-    pub size: u32,
+    pub size: u64,
+}
+
+/// The URL of Mojang's CDN for asset objects
+pub const RESOURCES_BASE_URL: &str = "https://resources.download.minecraft.net";
+
+impl Asset {
+    /// The path of the asset relative to the base URL of the asset CDN and to
+    /// the `objects` directory, `<first two characters of the hash>/<hash>`
+    pub fn path(&self) -> String {
+        format!("{}/{}", self.hash.get(..2).unwrap_or(&self.hash), self.hash)
+    }
+
+    /// The URL of the asset on the CDN with the base URL, such as
+    /// [`RESOURCES_BASE_URL`] or a mirror of it
+    pub fn url(&self, base_url: &str) -> String {
+        format!("{}/{}", base_url.trim_end_matches('/'), self.path())
+    }
 }
 
 #[cfg_attr(feature = "bincode", derive(Encode, Decode))]
@@ -542,18 +2630,354 @@ pub struct Asset {
 /// An index containing all assets the game needs
 pub struct AssetsIndex {
     /// A hashmap containing the filename (key) and asset (value)
-    pub objects: HashMap<String, Asset>,
+    pub objects: Map<String, Asset>,
+    #[serde(
+        default,
+        rename = "virtual",
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    /// Whether the assets are copied to their names in a virtual directory,
+    /// which is the case for the `legacy` index
+    pub virtual_: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    /// Whether the assets are copied to their names in the `resources`
+    /// directory of the game directory, which is the case for the `pre-1.6`
+    /// index
+    pub map_to_resources: bool,
+}
+
+impl AssetsIndex {
+    /// Returns how the assets of the index are laid out on disk
+    pub fn layout(&self) -> AssetLayout {
+        if self.map_to_resources {
+            AssetLayout::PreOneSix
+        } else if self.virtual_ {
+            AssetLayout::Legacy
+        } else {
+            AssetLayout::Modern
+        }
+    }
+
+    /// Returns the downloads of the asset objects of the index from the asset
+    /// CDN at the base URL, such as [`RESOURCES_BASE_URL`]. Objects shared by
+    /// several names are only listed once, the targets are sorted by hash
+    pub fn download_targets(
+        &self,
+        resources_base_url: &str,
+    ) -> Vec<AssetDownloadTarget> {
+        let mut visited = HashSet::new();
+        let mut targets = self
+            .objects
+            .values()
+            .filter(|x| visited.insert(&x.hash))
+            .map(|x| AssetDownloadTarget {
+                url: x.url(resources_base_url),
+                hash: x.hash.clone(),
+                size: x.size,
+                path: x.path(),
+            })
+            .collect::<Vec<_>>();
+        targets.sort_by(|a, b| a.hash.cmp(&b.hash));
+
+        targets
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The download of an asset object
+pub struct AssetDownloadTarget {
+    /// The URL the object is downloaded from
+    pub url: String,
+    /// The SHA1 hash of the object
+    pub hash: String,
+    /// The size of the object
+    pub size: u64,
+    /// The path of the object relative to the `objects` directory, see
+    /// [`Asset::path`]
+    pub path: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// How the assets of a version are laid out on disk
+pub enum AssetLayout {
+    /// The assets are stored by their hash in `assets/objects`
+    Modern,
+    /// Versions from 1.6 to 1.7.2 additionally need the assets copied to their
+    /// names in `assets/virtual/legacy`
+    Legacy,
+    /// Versions before 1.6 additionally need the assets copied to their names
+    /// in the `resources` directory of the game directory
+    PreOneSix,
+}
+
+/// Fetches the assets index of a version. The GDLauncher provided asset
+/// index of the manifest entry is preferred if present, otherwise the asset
+/// index of the version info is used
+pub async fn fetch_version_assets_index(
+    version: &Version,
+    info: &VersionInfo,
+) -> Result<AssetsIndex, Error> {
+    if version.assets_index_url.is_some() {
+        return version.fetch_assets_index().await;
+    }
+
+    fetch_assets_index(info).await
 }
 
 /// Fetches the assets index from the version info
 pub async fn fetch_assets_index(
     version: &VersionInfo,
 ) -> Result<AssetsIndex, Error> {
-    Ok(serde_json::from_slice(
-        &download_file(
-            &version.asset_index.url,
-            Some(&version.asset_index.sha1),
+    let index = &version.asset_index;
+
+    parse_json(
+        &index.url,
+        &download_metadata_with_size(
+            &index.url,
+            Some(&index.sha1),
+            Some(index.size),
         )
         .await?,
-    )?)
+    )
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> Vec<u8> {
+        std::fs::read(
+            Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("tests/fixtures")
+                .join(name),
+        )
+        .unwrap()
+    }
+
+    fn arch_rule(arch: &str) -> OsRule {
+        OsRule {
+            name: None,
+            version: None,
+            arch: Some(arch.to_string()),
+        }
+    }
+
+    /// Returns the path of the first `null` in the value
+    pub(crate) fn find_null(value: &serde_json::Value) -> Option<String> {
+        match value {
+            serde_json::Value::Null => Some(String::new()),
+            serde_json::Value::Array(values) => {
+                values.iter().enumerate().find_map(|(index, value)| {
+                    find_null(value).map(|path| format!("{}.{}", index, path))
+                })
+            }
+            serde_json::Value::Object(values) => {
+                values.iter().find_map(|(key, value)| {
+                    find_null(value).map(|path| format!("{}.{}", key, path))
+                })
+            }
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn manifest_without_compliance_level() {
+        let manifest: VersionManifest =
+            serde_json::from_slice(&fixture("version_manifest_v1.json"))
+                .unwrap();
+
+        assert_eq!(manifest.versions.len(), 3);
+        assert!(manifest.versions.iter().all(|x| x.compliance_level == 0));
+        assert!(manifest.versions.iter().all(|x| x.sha1.is_empty()));
+        assert_eq!(manifest.versions[2].type_, VersionType::OldAlpha);
+    }
+
+    #[test]
+    fn unknown_download_type_is_kept() {
+        let info: VersionInfo =
+            serde_json::from_slice(&fixture("unknown_download_type.json"))
+                .unwrap();
+        let other = DownloadType::Other("linux_server".to_string());
+
+        assert_eq!(info.downloads.len(), 3);
+        assert_eq!(info.downloads[&DownloadType::Client].size, 23028207);
+        assert_eq!(info.downloads[&other].size, 52368221);
+
+        let value = serde_json::to_value(&info).unwrap();
+        assert!(value["downloads"]["linux_server"].is_object());
+    }
+
+    #[test]
+    fn version_info_round_trip_has_no_nulls() {
+        let info: VersionInfo =
+            serde_json::from_slice(&fixture("1.19.json")).unwrap();
+        let value = serde_json::to_value(&info).unwrap();
+
+        assert_eq!(find_null(&value), None);
+        assert_eq!(serde_json::from_value::<VersionInfo>(value).unwrap(), info);
+    }
+
+    #[test]
+    fn legacy_version_info_round_trip_has_no_nulls() {
+        let info: VersionInfo = serde_json::from_value(serde_json::json!({
+            "assetIndex": {
+                "id": "legacy",
+                "sha1": "770572e819335b6c0a053f8378ad88eda189fc14",
+                "size": 109634,
+                "totalSize": 153475165,
+                "url": "https://launchermeta.mojang.com/v1/packages/770572e819335b6c0a053f8378ad88eda189fc14/legacy.json"
+            },
+            "assets": "legacy",
+            "downloads": {},
+            "id": "1.7.10",
+            "libraries": [{
+                "extract": { "exclude": ["META-INF/"] },
+                "name": "org.lwjgl.lwjgl:lwjgl-platform:2.9.1",
+                "natives": {
+                    "linux": "natives-linux",
+                    "windows": "natives-windows"
+                }
+            }],
+            "mainClass": "net.minecraft.client.main.Main",
+            "minecraftArguments": "--username ${auth_player_name}",
+            "minimumLauncherVersion": 13,
+            "releaseTime": "2014-05-14T17:29:23+00:00",
+            "time": "2014-05-14T17:29:23+00:00",
+            "type": "release"
+        }))
+        .unwrap();
+        let value = serde_json::to_value(&info).unwrap();
+
+        assert_eq!(find_null(&value), None);
+        assert_eq!(serde_json::from_value::<VersionInfo>(value).unwrap(), info);
+    }
+
+    #[test]
+    fn arch_only_rule_from_fixture() {
+        let info: VersionInfo =
+            serde_json::from_slice(&fixture("1.19.json")).unwrap();
+        let rules = info.arguments.as_ref().unwrap()[&ArgumentType::Jvm]
+            .iter()
+            .find_map(|x| match x {
+                Argument::Ruled {
+                    rules,
+                    value: ArgumentValue::Single(value),
+                } if value == "-Xss1M" => Some(rules),
+                _ => None,
+            })
+            .unwrap();
+        let features = FeatureSet::default();
+
+        let rule = rules[0].os.as_ref().unwrap();
+
+        assert_eq!(*rule, arch_rule("x86"));
+        assert_eq!(
+            rules_apply(rules, &Os::current(), &features),
+            cfg!(target_arch = "x86")
+        );
+        for os in [Os::Osx, Os::Windows, Os::Linux, Os::Unknown] {
+            assert!(rule.applies_on(&os, "x86"), "{:?}", os);
+            assert!(!rule.applies_on(&os, "x86_64"), "{:?}", os);
+            assert!(!rule.applies_on(&os, "aarch64"), "{:?}", os);
+        }
+    }
+
+    #[test]
+    fn current_os_has_the_arch_of_the_host() {
+        assert_eq!(Os::current().arch(), std::env::consts::ARCH);
+        assert_eq!(
+            Os::current().pointer_width(),
+            if cfg!(target_pointer_width = "32") {
+                "32"
+            } else {
+                "64"
+            }
+        );
+        assert_eq!(pointer_width("x86"), "32");
+    }
+
+    #[test]
+    fn arch_only_rule_matches_every_os_of_the_arch() {
+        let rule = arch_rule("aarch64");
+
+        assert!(rule.applies(&Os::OsxArm64));
+        assert!(rule.applies(&Os::WindowsArm64));
+        assert!(rule.applies(&Os::LinuxArm64));
+        assert!(!rule.applies(&Os::Linux));
+        assert!(!rule.applies(&Os::LinuxArm32));
+        assert!(arch_rule("arm").applies(&Os::LinuxArm32));
+        assert!(arch_rule("x86_64").applies(&Os::Windows));
+    }
+
+    #[test]
+    fn named_rule_with_arch_needs_both() {
+        let info: VersionInfo =
+            serde_json::from_slice(&fixture("1.19.json")).unwrap();
+        let library = info
+            .libraries
+            .iter()
+            .find(|x| x.name.ends_with(":natives-windows-x86"))
+            .unwrap();
+        let rule = library.rules.as_ref().unwrap()[0].os.as_ref().unwrap();
+
+        assert!(rule.applies_on(&Os::Windows, "x86"));
+        assert!(!rule.applies_on(&Os::Windows, "x86_64"));
+        assert!(!rule.applies_on(&Os::WindowsArm64, "aarch64"));
+        assert!(!rule.applies_on(&Os::Linux, "x86"));
+    }
+
+    #[test]
+    fn unknown_java_components_are_collected() {
+        let version = |component: &str, major_version: u32| {
+            let mut info: VersionInfo =
+                serde_json::from_slice(&fixture("1.19.json")).unwrap();
+            info.java_version = Some(JavaVersion {
+                component: component.to_string(),
+                major_version,
+            });
+
+            info
+        };
+        let versions = [
+            version("java-runtime-gamma", 17),
+            version("java-runtime-future", 25),
+            version("java-runtime-gamma", 18),
+        ];
+
+        let required = required_java_runtimes(&versions);
+
+        assert_eq!(
+            required.runtimes,
+            HashMap::from([(MinecraftJavaProfile::JavaRuntimeGamma, 18)])
+        );
+        assert_eq!(
+            required.unknown,
+            HashMap::from([("java-runtime-future".to_string(), 25)])
+        );
+    }
+
+    #[test]
+    fn native_classifier_uses_the_arch_of_the_os() {
+        let library: Library = serde_json::from_value(serde_json::json!({
+            "name": "org.lwjgl.lwjgl:lwjgl-platform:2.9.4",
+            "natives": {
+                "linux": "natives-linux-${arch}",
+                "windows": "natives-windows-${arch}"
+            }
+        }))
+        .unwrap();
+
+        assert_eq!(
+            library.native_classifier(&Os::WindowsArm64).as_deref(),
+            Some("natives-windows-64")
+        );
+        assert_eq!(
+            library.native_classifier(&Os::LinuxArm32).as_deref(),
+            Some("natives-linux-32")
+        );
+        assert_eq!(
+            library.native_classifier(&Os::LinuxArm64).as_deref(),
+            Some("natives-linux-64")
+        );
+    }
 }
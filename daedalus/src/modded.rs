@@ -1,11 +1,19 @@
-use crate::{download_file, Branding, Error, BRANDING};
+use crate::download::{Conditional, Validator};
+use crate::{
+    apply_game_version, check_format_version, download_file_conditional,
+    download_metadata, parse_json, Error, Map, MavenCoordinate, UnknownFields,
+};
 
 use crate::minecraft::{
-    Argument, ArgumentType, Library, VersionInfo, VersionType,
+    fetch_version_info, Argument, ArgumentType, Library, LibraryDownload,
+    LibraryDownloads, VersionInfo, VersionManifest, VersionType,
 };
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
 
 #[cfg(feature = "bincode")]
 use bincode::{Decode, Encode};
@@ -17,9 +25,25 @@ pub const CURRENT_FORGE_FORMAT_VERSION: usize = 0;
 /// The latest version of the format the quilt model structs deserialize to
 pub const CURRENT_QUILT_FORMAT_VERSION: usize = 0;
 
+/// The latest format version of any mod loader, which mod loader manifests are
+/// checked against when they are fetched
+const CURRENT_LOADER_FORMAT_VERSION: usize = max(
+    CURRENT_FABRIC_FORMAT_VERSION,
+    max(CURRENT_FORGE_FORMAT_VERSION, CURRENT_QUILT_FORMAT_VERSION),
+);
+
+/// Returns the larger of two format versions, usable in constants
+const fn max(a: usize, b: usize) -> usize {
+    if a > b {
+        a
+    } else {
+        b
+    }
+}
+
 /// A data variable entry that depends on the side of the installation
 #[cfg_attr(feature = "bincode", derive(Encode, Decode))]
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct SidedDataEntry {
     /// The value on the client
     pub client: String,
@@ -27,6 +51,77 @@ pub struct SidedDataEntry {
     pub server: String,
 }
 
+impl SidedDataEntry {
+    /// Returns the concrete value of the entry on the side, to substitute for
+    /// its key in the arguments of processors. Maven artifacts resolve to
+    /// their path in the libraries directory
+    pub fn resolve(
+        &self,
+        side: Side,
+        libraries_dir: &Path,
+    ) -> Result<String, Error> {
+        let value = match side {
+            Side::Client => &self.client,
+            Side::Server => &self.server,
+        };
+
+        Ok(match DataValue::parse(value)? {
+            DataValue::Maven(coordinate) => libraries_dir
+                .join(coordinate.path())
+                .to_string_lossy()
+                .into_owned(),
+            DataValue::Literal(value) | DataValue::Path(value) => value,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// A side of an installation
+pub enum Side {
+    /// The game client
+    Client,
+    /// A dedicated server
+    Server,
+}
+
+impl Side {
+    /// Converts the side to its name, as used by the `sides` of processors
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Side::Client => "client",
+            Side::Server => "server",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The value of a data entry, in the grammar of the forge installer
+pub enum DataValue {
+    /// A maven artifact, written as `[group:artifact:version]`
+    Maven(MavenCoordinate),
+    /// A literal string, written as `'value'`
+    Literal(String),
+    /// A path to a file in the installer, such as `/data/client.lzma`
+    Path(String),
+}
+
+impl DataValue {
+    /// Parses the value of a data entry
+    pub fn parse(value: &str) -> Result<DataValue, Error> {
+        if let Some(artifact) =
+            value.strip_prefix('[').and_then(|x| x.strip_suffix(']'))
+        {
+            Ok(DataValue::Maven(artifact.parse()?))
+        } else if let Some(literal) =
+            value.strip_prefix('\'').and_then(|x| x.strip_suffix('\''))
+        {
+            Ok(DataValue::Literal(literal.to_string()))
+        } else {
+            Ok(DataValue::Path(value.to_string()))
+        }
+    }
+}
+
 #[cfg_attr(feature = "bincode", derive(Encode, Decode))]
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -34,8 +129,10 @@ pub struct SidedDataEntry {
 pub struct PartialVersionInfo {
     /// The version ID of the version
     pub id: String,
-    /// The version ID this partial version inherits from
-    pub inherits_from: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// The version ID this partial version inherits from. Standalone profiles
+    /// which contain a complete version don't inherit from any
+    pub inherits_from: Option<String>,
     /// The time that the version was released
     #[cfg_attr(feature = "bincode", bincode(with_serde))]
     pub release_time: DateTime<Utc>,
@@ -50,7 +147,7 @@ pub struct PartialVersionInfo {
     pub minecraft_arguments: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     /// Arguments passed to the game or JVM
-    pub arguments: Option<HashMap<ArgumentType, Vec<Argument>>>,
+    pub arguments: Option<Map<ArgumentType, Vec<Argument>>>,
     /// Libraries that the version depends on
     pub libraries: Vec<Library>,
     #[serde(rename = "type")]
@@ -58,15 +155,208 @@ pub struct PartialVersionInfo {
     pub type_: VersionType,
     #[serde(skip_serializing_if = "Option::is_none")]
     /// (Forge-only)
-    pub data: Option<HashMap<String, SidedDataEntry>>,
+    pub data: Option<Map<String, SidedDataEntry>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     /// (Forge-only) The list of processors to run after downloading the files
     pub processors: Option<Vec<Processor>>,
+    #[serde(flatten)]
+    /// Fields which aren't modelled, such as `_comment_`, preserved so they
+    /// are serialized again
+    pub extra: UnknownFields,
+}
+
+impl PartialVersionInfo {
+    /// Converts a standalone profile, which inherits from no version, into
+    /// the complete version it contains. The fields of a complete version
+    /// which aren't part of a partial one are read from the unmodelled
+    /// fields, failing if they are missing
+    pub fn into_version_info(self) -> Result<VersionInfo, Error> {
+        Ok(serde_json::from_value(serde_json::to_value(self)?)?)
+    }
+
+    /// Deserializes a partial version from a reader, such as a buffered file,
+    /// without reading it into memory first
+    pub fn from_reader<R: std::io::Read>(
+        reader: R,
+    ) -> Result<PartialVersionInfo, Error> {
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    /// Returns the maven coordinates of the jars and classpath libraries of
+    /// every processor without duplicates, in the order they are first used,
+    /// to download them before running the processors
+    pub fn processor_libraries(&self) -> Vec<String> {
+        let mut visited = HashSet::new();
+
+        self.processors
+            .iter()
+            .flatten()
+            .flat_map(|x| std::iter::once(&x.jar).chain(&x.classpath))
+            .filter(|x| visited.insert(*x))
+            .cloned()
+            .collect()
+    }
+
+    /// Converts the partial version of a mod loader into its component of
+    /// the Prism Launcher/MultiMC metadata format, which is found by the
+    /// library of the loader, see [`PRISM_LOADERS`]. Fabric and Quilt require
+    /// the intermediary mappings component, whose library is left out, see
+    /// [`prism_intermediary_component`]. The other loaders require the
+    /// `net.minecraft` component of the version they inherit from. Every
+    /// loader conflicts with the other loaders
+    pub fn to_prism_component(&self) -> Result<String, Error> {
+        let (library, loader) = self
+            .libraries
+            .iter()
+            .find_map(|x| {
+                let coordinate = crate::maven_ga(&x.name).ok()?;
+
+                PRISM_LOADERS
+                    .iter()
+                    .find(|loader| loader.coordinate == coordinate)
+                    .map(|loader| (x, loader))
+            })
+            .ok_or_else(|| {
+                Error::ParseError(format!(
+                    "Unable to find the mod loader of version {}",
+                    self.id
+                ))
+            })?;
+
+        // Forge versions are prefixed with the game version, which the
+        // format leaves out
+        let coordinate = library.name.parse::<MavenCoordinate>()?;
+        let version = self
+            .inherits_from
+            .as_deref()
+            .and_then(|x| coordinate.version.strip_prefix(x)?.strip_prefix('-'))
+            .unwrap_or(&coordinate.version);
+
+        let requires = if loader.uses_intermediary {
+            vec![serde_json::json!({ "uid": PRISM_INTERMEDIARY_UID })]
+        } else {
+            self.inherits_from
+                .iter()
+                .map(|x| {
+                    serde_json::json!({ "uid": "net.minecraft", "equals": x })
+                })
+                .collect()
+        };
+        let conflicts = PRISM_LOADERS
+            .iter()
+            .filter(|x| x.uid != loader.uid)
+            .map(|x| serde_json::json!({ "uid": x.uid }))
+            .collect::<Vec<_>>();
+        let libraries = self
+            .libraries
+            .iter()
+            .filter(|x| {
+                !loader.uses_intermediary
+                    || crate::maven_ga(&x.name).ok().as_deref()
+                        != Some("net.fabricmc:intermediary")
+            })
+            .map(crate::minecraft::prism_library)
+            .collect::<Vec<_>>();
+
+        let mut component = serde_json::Map::new();
+        component.insert("formatVersion".to_string(), 1.into());
+        component.insert("name".to_string(), loader.name.into());
+        component.insert("uid".to_string(), loader.uid.into());
+        component.insert("version".to_string(), version.into());
+        component.insert("order".to_string(), loader.order.into());
+        component.insert(
+            "releaseTime".to_string(),
+            serde_json::to_value(self.release_time)?,
+        );
+        if !requires.is_empty() {
+            component.insert("requires".to_string(), requires.into());
+        }
+        component.insert("conflicts".to_string(), conflicts.into());
+        if let Some(main_class) = &self.main_class {
+            component
+                .insert("mainClass".to_string(), main_class.clone().into());
+        }
+        component.insert("libraries".to_string(), libraries.into());
+
+        Ok(serde_json::to_string_pretty(&component)?)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// A mod loader with a component in the Prism Launcher/MultiMC metadata
+/// format
+pub struct PrismLoader {
+    /// The `groupId:artifactId` of the library of the loader
+    pub coordinate: &'static str,
+    /// The UID of the component
+    pub uid: &'static str,
+    /// The name of the component
+    pub name: &'static str,
+    /// The order of the component among the components of an instance
+    pub order: i32,
+    /// Whether the component requires the intermediary mappings component
+    pub uses_intermediary: bool,
+}
+
+/// The mod loaders [`PartialVersionInfo::to_prism_component`] converts
+pub const PRISM_LOADERS: [PrismLoader; 4] = [
+    PrismLoader {
+        coordinate: "net.fabricmc:fabric-loader",
+        uid: "net.fabricmc.fabric-loader",
+        name: "Fabric Loader",
+        order: 10,
+        uses_intermediary: true,
+    },
+    PrismLoader {
+        coordinate: "org.quiltmc:quilt-loader",
+        uid: "org.quiltmc.quilt-loader",
+        name: "Quilt Loader",
+        order: 10,
+        uses_intermediary: true,
+    },
+    PrismLoader {
+        coordinate: "net.minecraftforge:forge",
+        uid: "net.minecraftforge",
+        name: "Forge",
+        order: 5,
+        uses_intermediary: false,
+    },
+    PrismLoader {
+        coordinate: "net.neoforged:neoforge",
+        uid: "net.neoforged",
+        name: "NeoForge",
+        order: 5,
+        uses_intermediary: false,
+    },
+];
+
+/// The UID of the intermediary mappings component of the Prism
+/// Launcher/MultiMC metadata format
+pub const PRISM_INTERMEDIARY_UID: &str = "net.fabricmc.intermediary";
+
+/// Creates the intermediary mappings component of the Prism Launcher/MultiMC
+/// metadata format for the game version, which Fabric and Quilt require and
+/// which requires the `net.minecraft` component of the game version
+pub fn prism_intermediary_component(
+    game_version: &str,
+) -> Result<String, Error> {
+    Ok(serde_json::to_string_pretty(&serde_json::json!({
+        "formatVersion": 1,
+        "name": "Intermediary Mappings",
+        "uid": PRISM_INTERMEDIARY_UID,
+        "version": game_version,
+        "order": 11,
+        "requires": [{ "uid": "net.minecraft", "equals": game_version }],
+        "libraries": [{
+            "name": format!("net.fabricmc:intermediary:{}", game_version),
+            "url": "https://maven.fabricmc.net/",
+        }],
+    }))?)
 }
 
 /// A processor to be ran after downloading the files
 #[cfg_attr(feature = "bincode", derive(Encode, Decode))]
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct Processor {
     /// Maven coordinates for the JAR library of this processor.
     pub jar: String,
@@ -76,7 +366,7 @@ pub struct Processor {
     pub args: Vec<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     /// Represents a map of outputs. Keys and values can be data values
-    pub outputs: Option<HashMap<String, String>>,
+    pub outputs: Option<Map<String, String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     /// Which sides this processor shall be ran on.
     /// Valid values: client, server, extract
@@ -87,24 +377,259 @@ pub struct Processor {
 pub async fn fetch_partial_version(
     url: &str,
 ) -> Result<PartialVersionInfo, Error> {
-    Ok(serde_json::from_slice(&download_file(url, None).await?)?)
+    parse_json(url, &download_metadata(url, None).await?)
+}
+
+/// Fetches the version manifest of a game version's URL, unless it has not
+/// changed since the fetch the validator is from
+pub async fn fetch_partial_version_conditional(
+    url: &str,
+    validator: Option<&Validator>,
+) -> Result<Conditional<PartialVersionInfo>, Error> {
+    download_file_conditional(url, validator)
+        .await?
+        .try_map(|bytes| parse_json(url, &bytes))
+}
+
+/// Reads the partial version embedded in a jar, such as a standalone OptiFine
+/// jar. This is the `version.json` at the root of the jar if it has one, or
+/// else the first JSON file at its root which deserializes to a partial
+/// version
+#[cfg(feature = "jar")]
+pub fn parse_partial_version_from_jar(
+    bytes: &[u8],
+) -> Result<PartialVersionInfo, Error> {
+    use std::io::Read;
+
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes))?;
+
+    let names = (0..archive.len())
+        .map(|i| Ok(archive.by_index_raw(i)?.name().to_string()))
+        .collect::<Result<Vec<_>, Error>>()?;
+    let mut read = |name: &str| -> Result<Vec<u8>, Error> {
+        let mut json = Vec::new();
+        archive.by_name(name)?.read_to_end(&mut json)?;
+
+        Ok(json)
+    };
+
+    if names.iter().any(|x| x == "version.json") {
+        return Ok(serde_json::from_slice(&read("version.json")?)?);
+    }
+
+    for name in names
+        .iter()
+        .filter(|x| !x.contains('/') && x.ends_with(".json"))
+    {
+        if let Ok(partial) = serde_json::from_slice(&read(name)?) {
+            return Ok(partial);
+        }
+    }
+
+    Err(Error::ParseError(
+        "Unable to find a partial version in the jar".to_string(),
+    ))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+/// The install profile embedded in a Forge or NeoForge installer jar since
+/// Minecraft 1.13, describing the libraries and processors installing it
+pub struct InstallProfile {
+    /// The version of the format of the install profile
+    pub spec: i32,
+    /// The name of the profile, such as `forge`
+    pub profile: String,
+    /// The ID of the version the installer installs
+    pub version: String,
+    /// The path of the version JSON in the installer
+    pub json: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// The maven coordinates of the library the installer contains, if it
+    /// contains one
+    pub path: Option<String>,
+    /// The game version the mod loader is for
+    pub minecraft: String,
+    #[serde(default)]
+    /// The data variables the arguments of the processors reference
+    pub data: Map<String, SidedDataEntry>,
+    /// The libraries needed to run the processors
+    pub libraries: Vec<Library>,
+    #[serde(default)]
+    /// The processors to run to install the mod loader
+    pub processors: Vec<Processor>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Where a library of a Forge or NeoForge installation comes from
+pub enum LibrarySource {
+    /// The library is downloaded
+    Download(LibraryDownload),
+    /// The library is contained in the installer jar, at the path
+    Installer(String),
+    /// The library is generated by the processors of the install profile,
+    /// and only exists after they ran
+    Processor,
+}
+
+impl InstallProfile {
+    /// Returns the paths of the maven artifacts the processors reference in
+    /// their arguments and outputs, directly or through data entries, except
+    /// the library the installer contains
+    pub fn processor_artifacts(&self) -> HashSet<String> {
+        let contained = self
+            .path
+            .as_deref()
+            .and_then(|x| crate::get_path_from_artifact(x).ok());
+
+        self.processors
+            .iter()
+            .flat_map(|x| {
+                x.args.iter().chain(x.outputs.iter().flat_map(|x| {
+                    x.iter().flat_map(|(key, value)| [key, value])
+                }))
+            })
+            .flat_map(|x| {
+                match x.strip_prefix('{').and_then(|x| x.strip_suffix('}')) {
+                    Some(key) => self
+                        .data
+                        .get(key)
+                        .map(|x| vec![&*x.client, &*x.server])
+                        .unwrap_or_default(),
+                    None => vec![&**x],
+                }
+            })
+            .filter_map(|x| match DataValue::parse(x) {
+                Ok(DataValue::Maven(coordinate)) => Some(coordinate.path()),
+                _ => None,
+            })
+            .filter(|x| Some(x) != contained.as_ref())
+            .collect()
+    }
+
+    /// Returns where a library of the version or the install profile comes
+    /// from. Libraries with a URL, or a repository to build one from, are
+    /// downloaded. Of those without, the ones the processors reference are
+    /// generated by them, and the others are contained in the installer
+    /// under `maven/`. `None` if the library's name isn't a maven artifact
+    pub fn library_source(
+        &self,
+        library: &Library,
+        default_repo: &str,
+    ) -> Option<LibrarySource> {
+        if !library.is_processor_output() {
+            return library
+                .resolve_download(default_repo)
+                .map(LibrarySource::Download);
+        }
+
+        let path = crate::get_path_from_artifact(&library.name).ok()?;
+
+        Some(if self.processor_artifacts().contains(&path) {
+            LibrarySource::Processor
+        } else {
+            LibrarySource::Installer(format!("maven/{}", path))
+        })
+    }
+}
+
+/// Reads the version JSON embedded in a Forge or NeoForge installer jar, the
+/// `version.json` at its root. Installers from before Minecraft 1.13 embed
+/// their version in their install profile instead and aren't supported
+#[cfg(feature = "jar")]
+pub fn extract_forge_version_json(
+    installer_bytes: &[u8],
+) -> Result<PartialVersionInfo, Error> {
+    Ok(serde_json::from_slice(&read_jar_file(
+        installer_bytes,
+        "version.json",
+    )?)?)
+}
+
+/// Reads the install profile embedded in a Forge or NeoForge installer jar,
+/// the `install_profile.json` at its root
+#[cfg(feature = "jar")]
+pub fn extract_install_profile(
+    installer_bytes: &[u8],
+) -> Result<InstallProfile, Error> {
+    Ok(serde_json::from_slice(&read_jar_file(
+        installer_bytes,
+        "install_profile.json",
+    )?)?)
 }
 
-/// Merges a partial version into a complete one
+/// Reads a file of a jar
+#[cfg(feature = "jar")]
+fn read_jar_file(bytes: &[u8], name: &str) -> Result<Vec<u8>, Error> {
+    use std::io::Read;
+
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes))?;
+    let mut contents = Vec::new();
+    archive.by_name(name)?.read_to_end(&mut contents)?;
+
+    Ok(contents)
+}
+
+/// Resolves a partial version into a complete one by fetching the version it
+/// inherits from in the manifest and merging the two. Standalone profiles,
+/// which inherit from no version, are complete already and aren't merged
+pub async fn resolve_partial(
+    partial: PartialVersionInfo,
+    manifest: &VersionManifest,
+) -> Result<VersionInfo, Error> {
+    let inherits_from = match &partial.inherits_from {
+        Some(inherits_from) => inherits_from,
+        None => return partial.into_version_info(),
+    };
+    let base = manifest
+        .versions
+        .iter()
+        .find(|x| x.id == *inherits_from)
+        .ok_or_else(|| Error::VersionNotFound {
+            id: inherits_from.clone(),
+        })?;
+
+    Ok(merge_partial_version(
+        partial,
+        fetch_version_info(base).await?,
+    ))
+}
+
+/// Replaces the placeholder for the game version in the path and URL of a
+/// library download
+fn apply_game_version_download(
+    download: LibraryDownload,
+    game_version: &str,
+) -> LibraryDownload {
+    LibraryDownload {
+        path: apply_game_version(&download.path, game_version),
+        url: apply_game_version(&download.url, game_version),
+        ..download
+    }
+}
+
+/// Merges a partial version into a complete one, whatever version the partial
+/// version inherits from. See [`resolve_partial`] for merging it into the
+/// version it inherits from
 pub fn merge_partial_version(
     partial: PartialVersionInfo,
     merge: VersionInfo,
 ) -> VersionInfo {
     let merge_id = merge.id.clone();
+    let inherited_main_class = partial
+        .main_class
+        .as_ref()
+        .filter(|x| **x != merge.main_class)
+        .map(|_| merge.main_class.clone());
 
     VersionInfo {
         arguments: if let Some(partial_args) = partial.arguments {
             if let Some(merge_args) = merge.arguments {
-                let mut new_map = HashMap::new();
+                let mut new_map = Map::new();
 
                 fn add_keys(
-                    new_map: &mut HashMap<ArgumentType, Vec<Argument>>,
-                    args: HashMap<ArgumentType, Vec<Argument>>,
+                    new_map: &mut Map<ArgumentType, Vec<Argument>>,
+                    args: Map<ArgumentType, Vec<Argument>>,
                 ) {
                     for (type_, arguments) in args {
                         for arg in arguments {
@@ -128,33 +653,42 @@ pub fn merge_partial_version(
             merge.arguments
         },
         asset_index: merge.asset_index,
+        compliance_level: merge.compliance_level,
         assets: merge.assets,
         downloads: merge.downloads,
-        id: partial.id.replace(
-            &BRANDING.get_or_init(Branding::default).dummy_replace_string,
-            &merge_id,
-        ),
+        id: apply_game_version(&partial.id, &merge_id),
         java_version: merge.java_version,
         libraries: partial
             .libraries
             .into_iter()
             .chain(merge.libraries)
             .map(|x| Library {
-                downloads: x.downloads,
+                downloads: x.downloads.map(|downloads| LibraryDownloads {
+                    artifact: downloads
+                        .artifact
+                        .map(|x| apply_game_version_download(x, &merge_id)),
+                    classifiers: downloads.classifiers.map(|classifiers| {
+                        classifiers
+                            .into_iter()
+                            .map(|(classifier, x)| {
+                                (
+                                    classifier,
+                                    apply_game_version_download(x, &merge_id),
+                                )
+                            })
+                            .collect()
+                    }),
+                }),
                 extract: x.extract,
-                name: x.name.replace(
-                    &BRANDING
-                        .get_or_init(Branding::default)
-                        .dummy_replace_string,
-                    &merge_id,
-                ),
-                url: x.url,
+                name: apply_game_version(&x.name, &merge_id),
+                url: x.url.map(|x| apply_game_version(&x, &merge_id)),
                 natives: x.natives,
                 rules: x.rules,
                 checksums: x.checksums,
                 include_in_classpath: x.include_in_classpath,
             })
             .collect::<Vec<_>>(),
+        inherited_main_class,
         main_class: if let Some(main_class) = partial.main_class {
             main_class
         } else {
@@ -167,6 +701,9 @@ pub fn merge_partial_version(
         type_: partial.type_,
         data: partial.data,
         processors: partial.processors,
+        extra: UnknownFields(
+            merge.extra.0.into_iter().chain(partial.extra.0).collect(),
+        ),
     }
 }
 
@@ -175,10 +712,23 @@ pub fn merge_partial_version(
 #[serde(rename_all = "camelCase")]
 /// A manifest containing information about a mod loader's versions
 pub struct Manifest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// The format version of the manifest, such as
+    /// [`CURRENT_FORGE_FORMAT_VERSION`] for the manifest of Forge. Only present
+    /// in manifests generated with this crate
+    pub format_version: Option<usize>,
     /// The game versions the mod loader supports
     pub game_versions: Vec<Version>,
 }
 
+impl Manifest {
+    /// Deserializes a manifest from a reader, such as a buffered file, without
+    /// reading it into memory first
+    pub fn from_reader<R: std::io::Read>(reader: R) -> Result<Manifest, Error> {
+        Ok(serde_json::from_reader(reader)?)
+    }
+}
+
 #[cfg_attr(feature = "bincode", derive(Encode, Decode))]
 #[derive(Serialize, Deserialize, Debug, Clone)]
 ///  A game version of Minecraft
@@ -187,10 +737,62 @@ pub struct Version {
     pub id: String,
     /// Whether the release is stable or not
     pub stable: bool,
-    /// A map that contains loader versions for the game version
+    #[serde(deserialize_with = "deserialize_loaders")]
+    /// A map that contains loader versions for the game version.
+    /// Also deserializes from a map of loader versions, such as one keyed by
+    /// `stable` and `latest`, keeping the order of the map
     pub loaders: Vec<LoaderVersion>,
 }
 
+/// Deserializes loader versions from either a list or a map of them
+fn deserialize_loaders<'de, D>(
+    deserializer: D,
+) -> Result<Vec<LoaderVersion>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    struct LoadersVisitor;
+
+    impl<'de> serde::de::Visitor<'de> for LoadersVisitor {
+        type Value = Vec<LoaderVersion>;
+
+        fn expecting(
+            &self,
+            formatter: &mut std::fmt::Formatter,
+        ) -> std::fmt::Result {
+            formatter.write_str("a list or a map of loader versions")
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+        where
+            A: serde::de::SeqAccess<'de>,
+        {
+            let mut loaders = Vec::new();
+            while let Some(loader) = seq.next_element()? {
+                loaders.push(loader);
+            }
+
+            Ok(loaders)
+        }
+
+        fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+        where
+            A: serde::de::MapAccess<'de>,
+        {
+            let mut loaders = Vec::new();
+            while let Some((_, loader)) =
+                map.next_entry::<serde::de::IgnoredAny, LoaderVersion>()?
+            {
+                loaders.push(loader);
+            }
+
+            Ok(loaders)
+        }
+    }
+
+    deserializer.deserialize_any(LoadersVisitor)
+}
+
 #[cfg_attr(feature = "bincode", derive(Encode, Decode))]
 #[derive(Serialize, Deserialize, Debug, Clone)]
 /// A version of a Minecraft mod loader
@@ -204,6 +806,307 @@ pub struct LoaderVersion {
 }
 
 /// Fetches the manifest of a mod loader
+/// Fails if the manifest has a newer format version than any mod loader
+/// supports
 pub async fn fetch_manifest(url: &str) -> Result<Manifest, Error> {
-    Ok(serde_json::from_slice(&download_file(url, None).await?)?)
+    let manifest: Manifest =
+        parse_json(url, &download_metadata(url, None).await?)?;
+
+    check_format_version(
+        manifest.format_version,
+        CURRENT_LOADER_FORMAT_VERSION,
+    )?;
+
+    Ok(manifest)
+}
+
+/// Fetches the partial versions of every loader version of a manifest, with up
+/// to the given amount of fetches at the same time. Each result is tagged with
+/// the ID of its loader version, the results are sorted by it. Loader versions
+/// with the same URL are only fetched once
+pub async fn fetch_all_partials(
+    manifest: &Manifest,
+    concurrency: usize,
+) -> Vec<(String, Result<PartialVersionInfo, Error>)> {
+    let semaphore = Arc::new(Semaphore::new(concurrency.max(1)));
+    let mut visited = HashSet::new();
+
+    let tasks = manifest
+        .game_versions
+        .iter()
+        .flat_map(|x| &x.loaders)
+        .filter(|x| visited.insert(&x.url))
+        .map(|loader| {
+            let semaphore = semaphore.clone();
+            let url = loader.url.clone();
+            let task = tokio::spawn(async move {
+                let _permit = semaphore.acquire_owned().await?;
+
+                fetch_partial_version(&url).await
+            });
+
+            (loader.id.clone(), task)
+        })
+        .collect::<Vec<_>>();
+
+    let mut partials = Vec::with_capacity(tasks.len());
+    for (id, task) in tasks {
+        partials.push((id, task.await.unwrap_or_else(|x| Err(x.into()))));
+    }
+    partials.sort_by(|a, b| a.0.cmp(&b.0));
+
+    partials
+}
+
+/// Fetches the manifest of a mod loader, unless it has not changed since the
+/// fetch the validator is from, to poll it for new loader versions
+pub async fn fetch_manifest_conditional(
+    url: &str,
+    validator: Option<&Validator>,
+) -> Result<Conditional<Manifest>, Error> {
+    download_file_conditional(url, validator)
+        .await?
+        .try_map(|bytes| {
+            let manifest: Manifest = parse_json(url, &bytes)?;
+
+            check_format_version(
+                manifest.format_version,
+                CURRENT_LOADER_FORMAT_VERSION,
+            )?;
+
+            Ok(manifest)
+        })
+}
+
+/// The URL to the fabric meta API
+pub const FABRIC_META_URL: &str = "https://meta.fabricmc.net/v2";
+/// The URL to the quilt meta API
+pub const QUILT_META_URL: &str = "https://meta.quiltmc.org/v3";
+
+#[cfg_attr(feature = "bincode", derive(Encode, Decode))]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+/// A game version supported by a mod loader, as listed by its meta API
+pub struct GameVersion {
+    /// The minecraft version ID
+    pub version: String,
+    /// Whether the game version is stable or not
+    pub stable: bool,
+}
+
+/// Fetches the game versions supported by fabric
+pub async fn fetch_fabric_game_versions() -> Result<Vec<GameVersion>, Error> {
+    fetch_game_versions(FABRIC_META_URL).await
+}
+
+/// Fetches the game versions supported by quilt
+pub async fn fetch_quilt_game_versions() -> Result<Vec<GameVersion>, Error> {
+    fetch_game_versions(QUILT_META_URL).await
+}
+
+/// Fetches the game versions of a fabric-like meta API
+async fn fetch_game_versions(
+    meta_url: &str,
+) -> Result<Vec<GameVersion>, Error> {
+    let url = format!("{}/versions/game", meta_url);
+
+    parse_json(&url, &download_metadata(&url, None).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::minecraft::{rules_apply, ArgumentValue, FeatureSet, Os};
+
+    fn fixture(name: &str) -> Vec<u8> {
+        std::fs::read(
+            Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("tests/fixtures")
+                .join(name),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn partial_version_round_trip_has_no_nulls() {
+        let partial =
+            partial("1.20.1", &["net.fabricmc:fabric-loader:0.14.21"]);
+        let value = serde_json::to_value(&partial).unwrap();
+
+        assert_eq!(crate::minecraft::tests::find_null(&value), None);
+    }
+
+    #[test]
+    fn game_versions_from_fixture() {
+        let versions: Vec<GameVersion> =
+            parse_json("fixture", &fixture("fabric_game_versions.json"))
+                .unwrap();
+
+        assert_eq!(versions.len(), 6);
+        assert_eq!(
+            versions[1],
+            GameVersion {
+                version: "1.20.1".to_string(),
+                stable: true,
+            }
+        );
+        assert_eq!(
+            versions
+                .iter()
+                .filter(|x| x.stable)
+                .map(|x| &*x.version)
+                .collect::<Vec<_>>(),
+            ["1.20.1", "1.20", "1.14"]
+        );
+    }
+
+    fn partial(inherits_from: &str, libraries: &[&str]) -> PartialVersionInfo {
+        serde_json::from_value(serde_json::json!({
+            "id": "loader",
+            "inheritsFrom": inherits_from,
+            "releaseTime": "2023-06-12T00:00:00+00:00",
+            "time": "2023-06-12T00:00:00+00:00",
+            "mainClass": "Main",
+            "type": "release",
+            "libraries": libraries
+                .iter()
+                .map(|x| serde_json::json!({ "name": x }))
+                .collect::<Vec<_>>(),
+        }))
+        .unwrap()
+    }
+
+    fn component(partial: &PartialVersionInfo) -> serde_json::Value {
+        serde_json::from_str(&partial.to_prism_component().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn fabric_prism_component_requires_intermediary() {
+        let component = component(&partial(
+            "1.20.1",
+            &[
+                "net.fabricmc:intermediary:1.20.1",
+                "net.fabricmc:fabric-loader:0.14.21",
+            ],
+        ));
+
+        assert_eq!(component["uid"], "net.fabricmc.fabric-loader");
+        assert_eq!(component["version"], "0.14.21");
+        assert_eq!(
+            component["requires"],
+            serde_json::json!([{ "uid": "net.fabricmc.intermediary" }])
+        );
+        assert!(component["conflicts"].as_array().unwrap().contains(
+            &serde_json::json!({ "uid": "org.quiltmc.quilt-loader" })
+        ));
+        assert_eq!(
+            component["libraries"],
+            serde_json::json!([{ "name": "net.fabricmc:fabric-loader:0.14.21" }])
+        );
+    }
+
+    #[test]
+    fn forge_prism_component_requires_minecraft() {
+        let component = component(&partial(
+            "1.20.1",
+            &["net.minecraftforge:forge:1.20.1-47.2.0:universal"],
+        ));
+
+        assert_eq!(component["uid"], "net.minecraftforge");
+        assert_eq!(component["version"], "47.2.0");
+        assert_eq!(
+            component["requires"],
+            serde_json::json!([{ "uid": "net.minecraft", "equals": "1.20.1" }])
+        );
+    }
+
+    #[cfg(feature = "jar")]
+    #[test]
+    fn partial_version_from_optifine_jar() {
+        let partial =
+            parse_partial_version_from_jar(&fixture("optifine.jar")).unwrap();
+
+        assert_eq!(partial.id, "1.20.1-OptiFine_HD_U_I6");
+        assert_eq!(partial.inherits_from.as_deref(), Some("1.20.1"));
+        assert_eq!(
+            partial.minecraft_arguments.as_deref(),
+            Some("--tweakClass optifine.OptiFineTweaker")
+        );
+        assert_eq!(partial.libraries.len(), 2);
+    }
+
+    #[cfg(feature = "jar")]
+    #[test]
+    fn partial_version_from_jar_rejects_other_files() {
+        assert!(parse_partial_version_from_jar(b"not a jar").is_err());
+    }
+
+    #[test]
+    fn jvm_arguments_without_rules_always_apply() {
+        let partial: PartialVersionInfo =
+            parse_json("fixture", &fixture("jvm_arguments_without_rules.json"))
+                .unwrap();
+        let jvm = &partial.arguments.as_ref().unwrap()[&ArgumentType::Jvm];
+        let applied = |os: &Os| {
+            jvm.iter()
+                .filter(|x| match x {
+                    Argument::Normal(_) => true,
+                    Argument::Ruled { rules, .. } => {
+                        rules_apply(rules, os, &FeatureSet::default())
+                    }
+                })
+                .count()
+        };
+
+        assert_eq!(jvm.len(), 4);
+        assert_eq!(
+            jvm[2],
+            Argument::Ruled {
+                rules: Vec::new(),
+                value: ArgumentValue::Single(
+                    "-DlibraryDirectory=${library_directory}".to_string()
+                ),
+            }
+        );
+        assert_eq!(applied(&Os::Linux), 3);
+        assert_eq!(applied(&Os::Osx), 4);
+    }
+
+    #[cfg(feature = "jar")]
+    #[test]
+    fn forge_installer_files_from_jar() {
+        let installer = fixture("forge_installer.jar");
+        let version = extract_forge_version_json(&installer).unwrap();
+        let profile = extract_install_profile(&installer).unwrap();
+
+        assert_eq!(version.id, "1.13.2-forge-25.0.219");
+        assert_eq!(version.inherits_from.as_deref(), Some("1.13.2"));
+        assert_eq!(version.libraries.len(), 2);
+
+        assert_eq!(profile.version, version.id);
+        assert_eq!(profile.minecraft, "1.13.2");
+        assert_eq!(profile.json, "/version.json");
+        assert_eq!(profile.data["BINPATCH"].client, "/data/client.lzma");
+        assert_eq!(profile.processors.len(), 2);
+        assert_eq!(profile.processors[1].sides, Some(vec!["client".into()]));
+    }
+
+    #[tokio::test]
+    async fn standalone_profile_resolves_without_merging() {
+        let partial: PartialVersionInfo =
+            parse_json("fixture", &fixture("standalone_profile.json")).unwrap();
+        // An empty manifest, so that resolving can't fetch a base version
+        let manifest: VersionManifest =
+            serde_json::from_value(serde_json::json!({
+                "latest": { "release": "1.12.2", "snapshot": "1.12.2" },
+                "versions": [],
+            }))
+            .unwrap();
+
+        assert_eq!(partial.inherits_from, None);
+
+        let info = resolve_partial(partial, &manifest).await.unwrap();
+        assert_eq!(info.id, "custom-1.12.2");
+        assert_eq!(info.asset_index.id, "1.12");
+        assert_eq!(info.libraries.len(), 2);
+    }
 }
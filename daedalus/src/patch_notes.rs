@@ -0,0 +1,117 @@
+use crate::minecraft::{Version, VersionInfo, VersionType};
+use crate::{download_metadata, parse_json, Error};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// The base URL of the launcher content, which the paths of the patch notes
+/// are relative to
+pub const PATCH_NOTES_BASE_URL: &str = "https://launchercontent.mojang.com";
+
+/// The URL of the Java Edition patch notes
+pub const PATCH_NOTES_URL: &str =
+    "https://launchercontent.mojang.com/v2/javaPatchNotes.json";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+/// The patch notes of the game versions
+pub struct PatchNotes {
+    /// The version of the format of the patch notes
+    pub version: u32,
+    /// The patch notes of each game version
+    pub entries: Vec<PatchNote>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+/// The patch notes of a game version
+pub struct PatchNote {
+    /// The unique identifier of the patch notes
+    pub id: String,
+    /// The title of the patch notes
+    pub title: String,
+    #[serde(rename = "type")]
+    /// The type of the game version
+    pub type_: VersionType,
+    /// The game version the patch notes are of
+    pub version: String,
+    /// The image shown alongside the patch notes
+    pub image: PatchNoteImage,
+    /// The path of the content of the patch notes, relative to
+    /// [`PATCH_NOTES_BASE_URL`]
+    pub content_path: String,
+    /// The time the patch notes were published
+    pub date: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+/// An image shown alongside patch notes
+pub struct PatchNoteImage {
+    /// The title of the image
+    pub title: String,
+    /// The path of the image, relative to [`PATCH_NOTES_BASE_URL`]
+    pub url: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+/// The content of patch notes
+pub struct PatchNoteContent {
+    /// The HTML body of the patch notes
+    pub body: String,
+}
+
+impl PatchNote {
+    /// The URL of the content of the patch notes
+    pub fn content_url(&self) -> String {
+        format!("{}{}", PATCH_NOTES_BASE_URL, self.content_path)
+    }
+
+    /// The URL of the image of the patch notes
+    pub fn image_url(&self) -> String {
+        format!("{}{}", PATCH_NOTES_BASE_URL, self.image.url)
+    }
+}
+
+impl Version {
+    /// Looks up the patch notes of the version
+    pub fn patch_notes<'a>(
+        &self,
+        notes: &'a HashMap<String, PatchNote>,
+    ) -> Option<&'a PatchNote> {
+        notes.get(&self.id)
+    }
+}
+
+impl VersionInfo {
+    /// Looks up the patch notes of the version
+    pub fn patch_notes<'a>(
+        &self,
+        notes: &'a HashMap<String, PatchNote>,
+    ) -> Option<&'a PatchNote> {
+        notes.get(&self.id)
+    }
+}
+
+/// Fetches the patch notes from the specified URL, keyed by the game version
+/// they are of. If no URL is specified, the default is used
+pub async fn fetch_patch_notes(
+    url: Option<&str>,
+) -> Result<HashMap<String, PatchNote>, Error> {
+    let url = url.unwrap_or(PATCH_NOTES_URL);
+    let notes: PatchNotes =
+        parse_json(url, &download_metadata(url, None).await?)?;
+
+    Ok(notes
+        .entries
+        .into_iter()
+        .map(|x| (x.version.clone(), x))
+        .collect())
+}
+
+/// Fetches the content of patch notes
+pub async fn fetch_patch_note_content(
+    note: &PatchNote,
+) -> Result<PatchNoteContent, Error> {
+    let url = note.content_url();
+
+    parse_json(&url, &download_metadata(&url, None).await?)
+}
@@ -0,0 +1,276 @@
+use crate::layout::{Layout, MojangLayout};
+use crate::minecraft::{
+    fetch_assets_index, fetch_version_info, fetch_version_manifest,
+    AssetsIndex, Version, VersionInfo, VersionManifest, CURRENT_FORMAT_VERSION,
+    VERSION_MANIFEST_URL,
+};
+use crate::{
+    check_format_version, download_metadata_with_size, parse_json, Error,
+};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+
+/// The future returned by the methods of a [`MetadataSource`], boxed so that
+/// sources can be used as trait objects
+pub type SourceFuture<'a, T> =
+    Pin<Box<dyn Future<Output = Result<T, Error>> + Send + 'a>>;
+
+/// A source of the game metadata, such as the official endpoints, a mirror of
+/// them or files cached on disk
+pub trait MetadataSource: std::fmt::Debug + Send + Sync {
+    /// Fetches the version manifest
+    fn version_manifest(&self) -> SourceFuture<'_, VersionManifest>;
+
+    /// Fetches detailed information about a version from the manifest
+    fn version_info<'a>(
+        &'a self,
+        version: &'a Version,
+    ) -> SourceFuture<'a, VersionInfo>;
+
+    /// Fetches the assets index of a version
+    fn assets_index<'a>(
+        &'a self,
+        info: &'a VersionInfo,
+    ) -> SourceFuture<'a, AssetsIndex>;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+/// The official Mojang endpoints
+pub struct MojangSource;
+
+impl MetadataSource for MojangSource {
+    fn version_manifest(&self) -> SourceFuture<'_, VersionManifest> {
+        Box::pin(fetch_version_manifest(None))
+    }
+
+    fn version_info<'a>(
+        &'a self,
+        version: &'a Version,
+    ) -> SourceFuture<'a, VersionInfo> {
+        Box::pin(fetch_version_info(version))
+    }
+
+    fn assets_index<'a>(
+        &'a self,
+        info: &'a VersionInfo,
+    ) -> SourceFuture<'a, AssetsIndex> {
+        Box::pin(fetch_assets_index(info))
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Mirrors of the official endpoints, serving the files at the same paths.
+/// The mirrors are tried in order until one of them succeeds
+pub struct MirrorSource {
+    /// The base URLs of the mirrors, without a trailing slash
+    pub base_urls: Vec<String>,
+}
+
+impl MirrorSource {
+    /// Downloads the metadata file at the official URL from the mirrors
+    async fn download(
+        &self,
+        url: &str,
+        sha1: Option<&str>,
+        size: Option<u64>,
+    ) -> Result<bytes::Bytes, Error> {
+        let parsed = reqwest::Url::parse(url).map_err(|err| {
+            Error::ParseError(format!("Invalid URL {}: {}", url, err))
+        })?;
+        if self.base_urls.is_empty() {
+            return Err(Error::ParseError("No mirrors provided!".to_string()));
+        }
+
+        let path = match parsed.query() {
+            Some(query) => format!("{}?{}", parsed.path(), query),
+            None => parsed.path().to_string(),
+        };
+
+        for (index, mirror) in self.base_urls.iter().enumerate() {
+            let url = format!("{}{}", mirror, path);
+            let result = download_metadata_with_size(&url, sha1, size).await;
+
+            if result.is_ok() || index == self.base_urls.len() - 1 {
+                return result;
+            }
+        }
+
+        unreachable!()
+    }
+}
+
+impl MetadataSource for MirrorSource {
+    fn version_manifest(&self) -> SourceFuture<'_, VersionManifest> {
+        Box::pin(async move {
+            let bytes = self.download(VERSION_MANIFEST_URL, None, None).await?;
+            let manifest: VersionManifest =
+                parse_json(VERSION_MANIFEST_URL, &bytes)?;
+
+            check_format_version(
+                manifest.format_version,
+                CURRENT_FORMAT_VERSION,
+            )?;
+
+            Ok(manifest)
+        })
+    }
+
+    fn version_info<'a>(
+        &'a self,
+        version: &'a Version,
+    ) -> SourceFuture<'a, VersionInfo> {
+        Box::pin(async move {
+            // Versions from the v1 manifest don't have a hash to validate
+            // against
+            let sha1 = Some(&*version.sha1).filter(|x| !x.is_empty());
+
+            parse_json(
+                &version.url,
+                &self.download(&version.url, sha1, None).await?,
+            )
+        })
+    }
+
+    fn assets_index<'a>(
+        &'a self,
+        info: &'a VersionInfo,
+    ) -> SourceFuture<'a, AssetsIndex> {
+        Box::pin(async move {
+            let index = &info.asset_index;
+
+            parse_json(
+                &index.url,
+                &self
+                    .download(&index.url, Some(&index.sha1), Some(index.size))
+                    .await?,
+            )
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Files cached on disk in the layout of the vanilla launcher, with the
+/// version manifest stored as `versions/version_manifest.json`
+pub struct LocalSource {
+    /// The root directory of the installation
+    pub root: PathBuf,
+}
+
+impl LocalSource {
+    /// Reads and parses the file
+    async fn read<T: serde::de::DeserializeOwned>(
+        path: &Path,
+    ) -> Result<T, Error> {
+        parse_json(&path.to_string_lossy(), &tokio::fs::read(path).await?)
+    }
+}
+
+impl MetadataSource for LocalSource {
+    fn version_manifest(&self) -> SourceFuture<'_, VersionManifest> {
+        Box::pin(async move {
+            let path = MojangLayout::new(&self.root)
+                .versions_dir()
+                .join("version_manifest.json");
+            let manifest: VersionManifest = LocalSource::read(&path).await?;
+
+            check_format_version(
+                manifest.format_version,
+                CURRENT_FORMAT_VERSION,
+            )?;
+
+            Ok(manifest)
+        })
+    }
+
+    fn version_info<'a>(
+        &'a self,
+        version: &'a Version,
+    ) -> SourceFuture<'a, VersionInfo> {
+        let path = MojangLayout::new(&self.root).version_info_path(&version.id);
+
+        Box::pin(async move { LocalSource::read(&path).await })
+    }
+
+    fn assets_index<'a>(
+        &'a self,
+        info: &'a VersionInfo,
+    ) -> SourceFuture<'a, AssetsIndex> {
+        let path = MojangLayout::new(&self.root).asset_index_path(info);
+
+        Box::pin(async move { LocalSource::read(&path).await })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::download::tests::serve_body;
+
+    #[tokio::test]
+    async fn mirrors_fall_back_to_the_next_one() {
+        // A port nothing listens on anymore, so connecting to it is refused
+        let down = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let (missing, missing_requests) = serve_body(&[404], "").await;
+        let manifest = r#"{
+            "latest": { "release": "1.20.1", "snapshot": "1.20.1" },
+            "versions": []
+        }"#;
+        let (serving, serving_requests) =
+            serve_body(&[200, 200], manifest).await;
+        let source = MirrorSource {
+            base_urls: vec![
+                format!("http://{}", down),
+                missing.trim_end_matches('/').to_string(),
+                serving.trim_end_matches('/').to_string(),
+            ],
+        };
+
+        let manifest = source.version_manifest().await.unwrap();
+        assert_eq!(manifest.latest.release, "1.20.1");
+        assert_eq!(missing_requests.lock().unwrap().len(), 1);
+
+        let url = "https://piston-meta.mojang.com/mc/game/manifest.json?x=1";
+        let mirrors = MirrorSource {
+            base_urls: source.base_urls[2..].to_vec(),
+        };
+        mirrors.download(url, None, None).await.unwrap();
+        let requests = serving_requests.lock().unwrap();
+        assert!(requests[1].starts_with("get /mc/game/manifest.json?x=1 "));
+    }
+
+    #[tokio::test]
+    async fn sources_are_dyn_compatible() {
+        let root = std::env::temp_dir()
+            .join(format!("daedalus-local-source-{}", std::process::id()));
+        let info = std::fs::read(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/1.19.json"
+        ))
+        .unwrap();
+        let info_path = MojangLayout::new(&root).version_info_path("1.19");
+        std::fs::create_dir_all(info_path.parent().unwrap()).unwrap();
+        std::fs::write(&info_path, info).unwrap();
+
+        let version: Version = serde_json::from_value(serde_json::json!({
+            "id": "1.19",
+            "type": "release",
+            "url": "https://piston-meta.mojang.com/1.19.json",
+            "time": "2022-06-07T09:42:18+00:00",
+            "releaseTime": "2022-06-07T09:42:18+00:00",
+        }))
+        .unwrap();
+        let sources: Vec<Box<dyn MetadataSource>> = vec![
+            Box::new(LocalSource { root: root.clone() }),
+            Box::new(MojangSource),
+        ];
+        let info = sources[0].version_info(&version).await;
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(info.unwrap().id, "1.19");
+    }
+}
@@ -0,0 +1,652 @@
+use crate::layout::{Layout, MojangLayout};
+use crate::minecraft::{
+    native_archive_path, AssetsIndex, DownloadType, FeatureSet, Os, VersionInfo,
+};
+use crate::{get_file_hash, get_path_from_artifact, Error};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+/// The amount of files hashed at the same time by [`audit_libraries`]
+const AUDIT_CONCURRENCY: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The reason a file of an installation failed its verification
+pub enum MissingReason {
+    /// The file does not exist
+    Absent,
+    /// The file has a different size than expected
+    WrongSize {
+        /// The size the file should have
+        expected: u64,
+        /// The size the file has
+        found: u64,
+    },
+    /// The file has a different SHA1 hash than expected
+    WrongHash {
+        /// The hash the file should have
+        expected: String,
+        /// The hash the file has
+        found: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A file of an installation which is missing or corrupt
+pub struct Missing {
+    /// The path of the file
+    pub path: PathBuf,
+    /// Why the file failed its verification
+    pub reason: MissingReason,
+}
+
+/// Verifies that the client jar, the libraries and natives needed on the OS
+/// with the given features and the assets of a version are installed in the
+/// game directory, using the layout of the vanilla launcher.
+/// Returns every missing or corrupt file instead of stopping at the first one
+pub async fn verify_installation(
+    info: &VersionInfo,
+    os: Os,
+    features: &FeatureSet,
+    game_dir: &Path,
+) -> Result<Vec<Missing>, Error> {
+    verify_installation_with_layout(
+        info,
+        &os,
+        features,
+        &MojangLayout::new(game_dir),
+    )
+    .await
+}
+
+/// Verifies the installation of a version like [`verify_installation`], with
+/// the files placed according to the layout
+pub async fn verify_installation_with_layout(
+    info: &VersionInfo,
+    os: &Os,
+    features: &FeatureSet,
+    layout: &dyn Layout,
+) -> Result<Vec<Missing>, Error> {
+    Ok(scan_installation(info, os, features, layout, false)
+        .await?
+        .missing)
+}
+
+/// Computes the amount of bytes left to download to install a version in the
+/// game directory, using the layout of the vanilla launcher. Only the files
+/// which are missing or fail their verification are counted. A quick check
+/// compares the sizes of the files without hashing them
+pub async fn remaining_download_size(
+    info: &VersionInfo,
+    os: Os,
+    features: &FeatureSet,
+    game_dir: &Path,
+    quick: bool,
+) -> Result<u64, Error> {
+    remaining_download_size_with_layout(
+        info,
+        &os,
+        features,
+        &MojangLayout::new(game_dir),
+        quick,
+    )
+    .await
+}
+
+/// Computes the amount of bytes left to download like
+/// [`remaining_download_size`], with the files placed according to the layout
+pub async fn remaining_download_size_with_layout(
+    info: &VersionInfo,
+    os: &Os,
+    features: &FeatureSet,
+    layout: &dyn Layout,
+    quick: bool,
+) -> Result<u64, Error> {
+    Ok(scan_installation(info, os, features, layout, quick)
+        .await?
+        .remaining)
+}
+
+/// The files of an installation which failed their verification
+struct Scan {
+    missing: Vec<Missing>,
+    /// The sum of the expected sizes of the missing files
+    remaining: u64,
+    /// Whether only the sizes of the files are checked
+    quick: bool,
+}
+
+impl Scan {
+    /// Checks the file, adding it to the missing files if it fails. An empty
+    /// hash or a zero size are treated as unknown and not checked.
+    /// Returns whether the file is intact
+    async fn check(
+        &mut self,
+        path: PathBuf,
+        sha1: &str,
+        size: u64,
+    ) -> Result<bool, Error> {
+        let sha1 = if self.quick { "" } else { sha1 };
+
+        match verify_file(&path, sha1, size).await? {
+            Some(reason) => {
+                self.missing.push(Missing { path, reason });
+                self.remaining += size;
+
+                Ok(false)
+            }
+            None => Ok(true),
+        }
+    }
+}
+
+/// Checks the client jar, the libraries and natives needed on the OS with the
+/// given features and the assets of a version
+async fn scan_installation(
+    info: &VersionInfo,
+    os: &Os,
+    features: &FeatureSet,
+    layout: &dyn Layout,
+    quick: bool,
+) -> Result<Scan, Error> {
+    let mut scan = Scan {
+        missing: Vec::new(),
+        remaining: 0,
+        quick,
+    };
+
+    if let Some(client) = info.downloads.get(&DownloadType::Client) {
+        let path = layout.client_jar_path(&info.id);
+        scan.check(path, &client.sha1, client.size).await?;
+    }
+
+    for library in info.libraries.iter().filter(|x| x.applies(os, features)) {
+        // Legacy natives-only libraries have no artifact of their own
+        let natives_only = library.natives.is_some()
+            && library
+                .downloads
+                .as_ref()
+                .is_none_or(|x| x.artifact.is_none());
+
+        if !natives_only {
+            if let Some(artifact) = library.resolve_download("") {
+                let path = layout.library_path(&library.name)?;
+                scan.check(path, &artifact.sha1, artifact.size).await?;
+            }
+        }
+
+        if let (Some(classifier), Some(native)) =
+            (library.native_classifier(os), library.native_download(os))
+        {
+            let path = layout.libraries_dir().join(native_archive_path(
+                &library.name,
+                &classifier,
+                native,
+            )?);
+            scan.check(path, &native.sha1, native.size).await?;
+        }
+    }
+
+    let index_path = layout.asset_index_path(info);
+    let index_valid = scan
+        .check(
+            index_path.clone(),
+            &info.asset_index.sha1,
+            info.asset_index.size,
+        )
+        .await?;
+
+    // The assets can only be verified against an intact index, otherwise all
+    // of them are left to download
+    if index_valid {
+        let index: AssetsIndex =
+            serde_json::from_slice(&tokio::fs::read(&index_path).await?)?;
+
+        let mut visited = HashSet::new();
+        for asset in index.objects.values() {
+            if visited.insert(&asset.hash) {
+                let path = layout.asset_object_path(&asset.hash);
+                scan.check(path, &asset.hash, asset.size).await?;
+            }
+        }
+    } else {
+        scan.remaining += info.asset_index.total_size;
+    }
+
+    Ok(scan)
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// The state of a libraries directory compared to the libraries of a version
+pub struct LibraryAudit {
+    /// The expected files which are intact
+    pub present: Vec<PathBuf>,
+    /// The expected files which do not exist
+    pub missing: Vec<PathBuf>,
+    /// The expected files which exist but have the wrong size or hash
+    pub corrupt: Vec<Missing>,
+    /// The files in the directory which no library of the version references
+    pub orphans: Vec<PathBuf>,
+}
+
+/// Audits a libraries directory against every library of a version, hashing
+/// the files concurrently. Rules are not evaluated, so the artifacts and
+/// natives of all OSes are expected, which makes the orphans safe to delete
+pub async fn audit_libraries(
+    info: &VersionInfo,
+    libraries_dir: &Path,
+) -> Result<LibraryAudit, Error> {
+    let mut expected = Vec::new();
+    let mut referenced = HashSet::new();
+
+    for library in &info.libraries {
+        let natives_only = library.natives.is_some()
+            && library
+                .downloads
+                .as_ref()
+                .is_none_or(|x| x.artifact.is_none());
+
+        if !natives_only {
+            if let Some(artifact) = library.resolve_download("") {
+                let path =
+                    libraries_dir.join(get_path_from_artifact(&library.name)?);
+                if referenced.insert(path.clone()) {
+                    expected.push((path, artifact.sha1, artifact.size));
+                }
+            }
+        }
+
+        let classifiers = library
+            .downloads
+            .as_ref()
+            .and_then(|x| x.classifiers.as_ref());
+        for (classifier, native) in classifiers.into_iter().flatten() {
+            let path = libraries_dir.join(native_archive_path(
+                &library.name,
+                classifier,
+                native,
+            )?);
+            if referenced.insert(path.clone()) {
+                expected.push((path, native.sha1.clone(), native.size));
+            }
+        }
+    }
+
+    let semaphore = Arc::new(Semaphore::new(AUDIT_CONCURRENCY));
+    let mut tasks = tokio::task::JoinSet::new();
+    for (path, sha1, size) in expected {
+        let semaphore = semaphore.clone();
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await?;
+            let reason = verify_file(&path, &sha1, size).await?;
+
+            Ok::<_, Error>((path, reason))
+        });
+    }
+
+    let mut audit = LibraryAudit::default();
+    while let Some(result) = tasks.join_next().await {
+        match result?? {
+            (path, None) => audit.present.push(path),
+            (path, Some(MissingReason::Absent)) => audit.missing.push(path),
+            (path, Some(reason)) => {
+                audit.corrupt.push(Missing { path, reason })
+            }
+        }
+    }
+
+    let root = libraries_dir.to_path_buf();
+    let files =
+        tokio::task::spawn_blocking(move || list_files(&root)).await??;
+    audit.orphans = files
+        .into_iter()
+        .filter(|x| !referenced.contains(x))
+        .collect();
+
+    audit.present.sort();
+    audit.missing.sort();
+    audit.corrupt.sort_by(|a, b| a.path.cmp(&b.path));
+    audit.orphans.sort();
+
+    Ok(audit)
+}
+
+/// Lists the files in the directory and its subdirectories, a directory which
+/// does not exist has no files
+fn list_files(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err.into()),
+        };
+
+        for entry in entries {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                dirs.push(entry.path());
+            } else {
+                files.push(entry.path());
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+/// Returns why the file fails its verification, if it does
+async fn verify_file(
+    path: &Path,
+    sha1: &str,
+    size: u64,
+) -> Result<Option<MissingReason>, Error> {
+    let metadata = match tokio::fs::metadata(path).await {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(Some(MissingReason::Absent))
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    if size != 0 && metadata.len() != size {
+        return Ok(Some(MissingReason::WrongSize {
+            expected: size,
+            found: metadata.len(),
+        }));
+    }
+
+    if !sha1.is_empty() {
+        let hash = get_file_hash(path).await?;
+
+        if !hash.eq_ignore_ascii_case(sha1) {
+            return Ok(Some(MissingReason::WrongHash {
+                expected: sha1.to_string(),
+                found: hash,
+            }));
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An installation of the 1.19 fixture in a temporary directory, whose
+    /// client jar, library and asset object are small files
+    struct Install {
+        root: PathBuf,
+        info: VersionInfo,
+        client: PathBuf,
+        library: PathBuf,
+        index: PathBuf,
+        asset: PathBuf,
+    }
+
+    fn hash(contents: &[u8]) -> String {
+        sha1::Sha1::from(contents).hexdigest()
+    }
+
+    fn write(path: &Path, contents: &[u8]) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+
+    fn install(name: &str) -> Install {
+        let root = std::env::temp_dir().join(format!(
+            "daedalus-verify-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&root);
+        let layout = MojangLayout::new(&root);
+
+        let mut info: VersionInfo = serde_json::from_slice(
+            &std::fs::read(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/fixtures/1.19.json"
+            ))
+            .unwrap(),
+        )
+        .unwrap();
+        info.libraries.truncate(1);
+
+        let client = info.downloads.get_mut(&DownloadType::Client).unwrap();
+        client.sha1 = hash(b"client");
+        client.size = 6;
+        let artifact = info.libraries[0]
+            .downloads
+            .as_mut()
+            .and_then(|x| x.artifact.as_mut())
+            .unwrap();
+        artifact.sha1 = hash(b"library");
+        artifact.size = 7;
+
+        let index = serde_json::to_vec(&serde_json::json!({
+            "objects": {
+                "minecraft/sounds/ambient.ogg": {
+                    "hash": hash(b"asset"),
+                    "size": 5,
+                },
+            },
+        }))
+        .unwrap();
+        info.asset_index.sha1 = hash(&index);
+        info.asset_index.size = index.len() as u64;
+        info.asset_index.total_size = 5;
+
+        let install = Install {
+            client: layout.client_jar_path(&info.id),
+            library: layout.library_path(&info.libraries[0].name).unwrap(),
+            index: layout.asset_index_path(&info),
+            asset: layout.asset_object_path(&hash(b"asset")),
+            root,
+            info,
+        };
+        write(&install.client, b"client");
+        write(&install.library, b"library");
+        write(&install.index, &index);
+        write(&install.asset, b"asset");
+
+        install
+    }
+
+    async fn verify(install: &Install) -> Vec<Missing> {
+        verify_installation(
+            &install.info,
+            Os::Linux,
+            &FeatureSet::default(),
+            &install.root,
+        )
+        .await
+        .unwrap()
+    }
+
+    async fn remaining(install: &Install, quick: bool) -> u64 {
+        remaining_download_size(
+            &install.info,
+            Os::Linux,
+            &FeatureSet::default(),
+            &install.root,
+            quick,
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn intact_installation_has_nothing_missing() {
+        let install = install("intact");
+
+        assert_eq!(verify(&install).await, Vec::new());
+        assert_eq!(remaining(&install, false).await, 0);
+        std::fs::remove_dir_all(&install.root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn broken_files_are_reported_with_their_reason() {
+        let install = install("broken");
+        let files: [(&Path, &[u8]); 3] = [
+            (&install.client, b"client"),
+            (&install.library, b"library"),
+            (&install.asset, b"asset"),
+        ];
+
+        for (path, _) in &files {
+            std::fs::remove_file(path).unwrap();
+        }
+        let absent = files
+            .iter()
+            .map(|(path, _)| Missing {
+                path: path.to_path_buf(),
+                reason: MissingReason::Absent,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(verify(&install).await, absent);
+
+        for (path, _) in &files {
+            write(path, b"resized file");
+        }
+        let resized = files
+            .iter()
+            .map(|(path, contents)| Missing {
+                path: path.to_path_buf(),
+                reason: MissingReason::WrongSize {
+                    expected: contents.len() as u64,
+                    found: 12,
+                },
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(verify(&install).await, resized);
+
+        for (path, contents) in &files {
+            write(path, &vec![b'x'; contents.len()]);
+        }
+        let corrupt = files
+            .iter()
+            .map(|(path, contents)| Missing {
+                path: path.to_path_buf(),
+                reason: MissingReason::WrongHash {
+                    expected: hash(contents),
+                    found: hash(&vec![b'x'; contents.len()]),
+                },
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(verify(&install).await, corrupt);
+        assert_eq!(remaining(&install, false).await, 6 + 7 + 5);
+        std::fs::remove_dir_all(&install.root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn corrupt_asset_index_leaves_every_asset_to_download() {
+        let mut install = install("corrupt-index");
+        install.info.asset_index.total_size = 1000;
+        write(&install.index, b"{}");
+
+        let index_size = install.info.asset_index.size;
+        assert_eq!(
+            verify(&install).await,
+            vec![Missing {
+                path: install.index.clone(),
+                reason: MissingReason::WrongSize {
+                    expected: index_size,
+                    found: 2,
+                },
+            }]
+        );
+        assert_eq!(remaining(&install, false).await, index_size + 1000);
+        std::fs::remove_dir_all(&install.root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn quick_check_only_compares_sizes() {
+        let install = install("quick");
+        write(&install.library, b"LIBRARY");
+
+        assert_eq!(remaining(&install, true).await, 0);
+        assert_eq!(remaining(&install, false).await, 7);
+        std::fs::remove_dir_all(&install.root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn audit_sorts_the_libraries_directory() {
+        let mut install = install("audit");
+        let libraries = install.root.join("libraries");
+        let native = |classifier: &str, contents: &[u8]| {
+            serde_json::json!({
+                "path": format!(
+                    "org/lwjgl/lwjgl/lwjgl-platform/2.9.4/\
+                     lwjgl-platform-2.9.4-{}.jar",
+                    classifier
+                ),
+                "sha1": hash(contents),
+                "size": contents.len(),
+                "url": "",
+            })
+        };
+        install.info.libraries.extend(
+            serde_json::from_value::<Vec<_>>(serde_json::json!([
+                {
+                    "name": "org.lwjgl.lwjgl:lwjgl-platform:2.9.4",
+                    "natives": {
+                        "linux": "natives-linux",
+                        "windows": "natives-windows",
+                    },
+                    "downloads": {
+                        "classifiers": {
+                            "natives-linux": native("natives-linux", b"linux"),
+                            "natives-windows":
+                                native("natives-windows", b"windows"),
+                        },
+                    },
+                },
+                {
+                    "name": "com.mojang:brigadier:1.0.18",
+                    "downloads": {
+                        "artifact": {
+                            "path": "com/mojang/brigadier/1.0.18/\
+                                     brigadier-1.0.18.jar",
+                            "sha1": hash(b"brigadier"),
+                            "size": 9,
+                            "url": "",
+                        },
+                    },
+                },
+            ]))
+            .unwrap(),
+        );
+
+        let platform = libraries.join("org/lwjgl/lwjgl/lwjgl-platform/2.9.4");
+        let linux = platform.join("lwjgl-platform-2.9.4-natives-linux.jar");
+        let windows = platform.join("lwjgl-platform-2.9.4-natives-windows.jar");
+        let orphan =
+            libraries.join("com/mojang/logging/0.9.0/logging-0.9.0.jar");
+        write(&linux, b"linux");
+        write(&windows, b"linux!!");
+        write(&orphan, b"logging");
+
+        let audit = audit_libraries(&install.info, &libraries).await.unwrap();
+        assert_eq!(
+            audit,
+            LibraryAudit {
+                present: vec![install.library.clone(), linux],
+                missing: vec![libraries
+                    .join("com/mojang/brigadier/1.0.18/brigadier-1.0.18.jar")],
+                corrupt: vec![Missing {
+                    path: windows,
+                    reason: MissingReason::WrongHash {
+                        expected: hash(b"windows"),
+                        found: hash(b"linux!!"),
+                    },
+                }],
+                orphans: vec![orphan],
+            }
+        );
+        std::fs::remove_dir_all(&install.root).unwrap();
+    }
+}
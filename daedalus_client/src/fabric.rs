@@ -193,15 +193,16 @@ pub async fn retrieve_data(
                     release_time: version.release_time,
                     time: version.time,
                     type_: version.type_,
-                    inherits_from: version
-                        .inherits_from
-                        .replace(DUMMY_GAME_VERSION, &BRANDING
+                    inherits_from: version.inherits_from.map(|x| {
+                        x.replace(DUMMY_GAME_VERSION, &BRANDING
                             .get_or_init(Branding::default)
-                            .dummy_replace_string),
+                            .dummy_replace_string)
+                    }),
                     libraries: libs,
                     minecraft_arguments: version.minecraft_arguments,
                     processors: None,
                     data: None,
+                    extra: version.extra,
                 })?,
                 Some("application/json".to_string()),
                 &uploaded_files_mutex,
@@ -296,6 +297,9 @@ pub async fn retrieve_data(
             daedalus::modded::CURRENT_FABRIC_FORMAT_VERSION,
         ),
         serde_json::to_vec(&Manifest {
+            format_version: Some(
+                daedalus::modded::CURRENT_FABRIC_FORMAT_VERSION,
+            ),
             game_versions: versions,
         })?,
         Some("application/json".to_string()),
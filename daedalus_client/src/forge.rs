@@ -9,6 +9,7 @@ use daedalus::minecraft::{
 use daedalus::modded::{
     LoaderVersion, Manifest, PartialVersionInfo, Processor, SidedDataEntry,
 };
+use daedalus::Map;
 use lazy_static::lazy_static;
 use log::info;
 use semver::{Version, VersionReq};
@@ -211,7 +212,7 @@ pub async fn retrieve_data(
 
                                     let new_profile = PartialVersionInfo {
                                         id: profile.version_info.id,
-                                        inherits_from: profile.install.minecraft,
+                                        inherits_from: Some(profile.install.minecraft),
                                         release_time: profile.version_info.release_time,
                                         time: profile.version_info.time,
                                         main_class: profile.version_info.main_class,
@@ -220,7 +221,8 @@ pub async fn retrieve_data(
                                         libraries: libs,
                                         type_: profile.version_info.type_,
                                         data: None,
-                                        processors: None
+                                        processors: None,
+                                        extra: Default::default(),
                                     };
 
                                     let version_path = format!(
@@ -437,6 +439,7 @@ pub async fn retrieve_data(
                                         type_: version_info.type_,
                                         data: Some(profile.data),
                                         processors: Some(profile.processors),
+                                        extra: version_info.extra,
                                     };
 
                                     let version_path = format!(
@@ -560,6 +563,9 @@ pub async fn retrieve_data(
                 daedalus::modded::CURRENT_FORGE_FORMAT_VERSION,
             ),
             serde_json::to_vec(&Manifest {
+                format_version: Some(
+                    daedalus::modded::CURRENT_FORGE_FORMAT_VERSION,
+                ),
                 game_versions: versions,
             })?,
             Some("application/json".to_string()),
@@ -644,7 +650,7 @@ struct ForgeInstallerProfileV2 {
     pub json: String,
     pub path: Option<String>,
     pub minecraft: String,
-    pub data: HashMap<String, SidedDataEntry>,
+    pub data: Map<String, SidedDataEntry>,
     pub libraries: Vec<Library>,
     pub processors: Vec<Processor>,
 }
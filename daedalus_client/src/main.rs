@@ -1,4 +1,4 @@
-use daedalus::{Branding, BRANDING};
+use daedalus::Branding;
 use log::{error, info, warn};
 use s3::creds::Credentials;
 use s3::error::S3Error;
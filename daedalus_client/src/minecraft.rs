@@ -27,6 +27,7 @@ pub async fn retrieve_data(
 
     let mut manifest =
         daedalus::minecraft::fetch_version_manifest(None).await?;
+    manifest.format_version = Some(daedalus::minecraft::CURRENT_FORMAT_VERSION);
 
     let cloned_manifest = Arc::new(Mutex::new(manifest.clone()));
 